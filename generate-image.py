@@ -84,6 +84,10 @@ except ImportError:
 # These will be set relative to the project root (where .env is found)
 OUTPUT_DIR = Path("generated_images")
 CONFIG_FILE = Path(".image-gen-config.json")
+
+# Version of the metadata JSON written next to each image.
+# Must match SCHEMA_VERSION in offers-studio/src-tauri/src/metadata.rs
+METADATA_SCHEMA_VERSION = 2
 PROJECT_ROOT = None  # Will be set by load_env()
 
 DEFAULT_CONFIG = {
@@ -226,7 +230,7 @@ def save_metadata(filepath: Path, metadata: dict):
     """Save generation metadata as JSON."""
     meta_path = filepath.with_suffix('.json')
     with open(meta_path, 'w') as f:
-        json.dump(metadata, f, indent=2)
+        json.dump({"schema_version": METADATA_SCHEMA_VERSION, **metadata}, f, indent=2)
 
 
 def generate_image(
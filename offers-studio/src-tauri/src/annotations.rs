@@ -0,0 +1,244 @@
+//! User annotations on gallery images: tags, ratings, favourites and notes
+//! Keyed by content hash so they follow an image through renames and moves,
+//! and kept apart from catalog.json since that file can be rebuilt at any time
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    /// Lowercase, sorted and unique
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// 1 to 5 stars
+    pub rating: Option<u8>,
+    #[serde(default)]
+    pub favorite: bool,
+    pub notes: Option<String>,
+}
+
+impl Annotation {
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.rating.is_none() && !self.favorite && self.notes.is_none()
+    }
+}
+
+/// Changes to apply to one or more images; fields left out are untouched
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AnnotationUpdate {
+    /// Replace all tags
+    pub tags: Option<Vec<String>>,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+    /// 1 to 5, or 0 to clear
+    pub rating: Option<u8>,
+    pub favorite: Option<bool>,
+    /// An empty string clears the notes
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().to_lowercase();
+    (!tag.is_empty()).then_some(tag)
+}
+
+impl AnnotationUpdate {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.rating {
+            Some(rating) if rating > 5 => Err(format!("Rating must be between 1 and 5, got {}", rating)),
+            _ => Ok(()),
+        }
+    }
+
+    fn apply(&self, annotation: &mut Annotation) {
+        if let Some(tags) = &self.tags {
+            annotation.tags = tags.iter().filter_map(|t| normalize_tag(t)).collect();
+        }
+        annotation.tags.extend(self.add_tags.iter().filter_map(|t| normalize_tag(t)));
+        let removed: Vec<String> = self.remove_tags.iter().filter_map(|t| normalize_tag(t)).collect();
+        annotation.tags.retain(|t| !removed.contains(t));
+        annotation.tags.sort();
+        annotation.tags.dedup();
+
+        if let Some(rating) = self.rating {
+            annotation.rating = (rating > 0).then_some(rating);
+        }
+        if let Some(favorite) = self.favorite {
+            annotation.favorite = favorite;
+        }
+        if let Some(notes) = &self.notes {
+            annotation.notes = (!notes.trim().is_empty()).then(|| notes.clone());
+        }
+    }
+}
+
+/// An annotation as stored, with the file it was last seen on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredAnnotation {
+    #[serde(flatten)]
+    annotation: Annotation,
+    /// (modified, size) of the annotated file, so a freshly indexed copy of it
+    /// can be recognised and hashed without hashing every image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    file_id: Option<(i64, u64)>,
+}
+
+/// A project's annotations, stored in .genimage-studio/annotations.json
+pub struct AnnotationStore {
+    path: PathBuf,
+    annotations: HashMap<String, StoredAnnotation>,
+}
+
+impl AnnotationStore {
+    pub fn load(studio_dir: &Path) -> Self {
+        let path = studio_dir.join("annotations.json");
+        let annotations = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self { path, annotations }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create studio directory: {}", e))?;
+        }
+        let content = serde_json::to_string_pretty(&self.annotations)
+            .map_err(|e| format!("Failed to serialize annotations: {}", e))?;
+
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, content)
+            .map_err(|e| format!("Failed to write annotations: {}", e))?;
+        fs::rename(&tmp_path, &self.path)
+            .map_err(|e| format!("Failed to replace annotations: {}", e))
+    }
+
+    pub fn get(&self, content_hash: &str) -> Option<&Annotation> {
+        self.annotations.get(content_hash).map(|stored| &stored.annotation)
+    }
+
+    /// Apply an update to an image's annotation, dropping it once it's empty
+    pub fn update(&mut self, content_hash: &str, file_id: (i64, u64), update: &AnnotationUpdate) {
+        let mut annotation = self.annotations
+            .remove(content_hash)
+            .map(|stored| stored.annotation)
+            .unwrap_or_default();
+        update.apply(&mut annotation);
+        if !annotation.is_empty() {
+            self.annotations.insert(content_hash.to_string(), StoredAnnotation {
+                annotation,
+                file_id: Some(file_id),
+            });
+        }
+    }
+
+    /// Whether a file with this (modified, size) may carry an annotation
+    pub fn may_be_annotated(&self, file_id: (i64, u64)) -> bool {
+        self.annotations.values().any(|stored| stored.file_id == Some(file_id))
+    }
+
+    /// Record the file an annotated hash was seen on. Returns true if that changed.
+    pub fn seen(&mut self, content_hash: &str, file_id: (i64, u64)) -> bool {
+        match self.annotations.get_mut(content_hash) {
+            Some(stored) if stored.file_id != Some(file_id) => {
+                stored.file_id = Some(file_id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Move an annotation to a new hash after the file's bytes were rewritten.
+    /// Returns true if there was one to move.
+    pub fn rekey(&mut self, old_hash: &str, new_hash: &str, file_id: (i64, u64)) -> bool {
+        match self.annotations.remove(old_hash) {
+            Some(mut stored) => {
+                stored.file_id = Some(file_id);
+                self.annotations.insert(new_hash.to_string(), stored);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Every tag on a live image and how many images carry it, most used first.
+    /// Annotations whose image is gone are kept (it may come back) but not counted.
+    pub fn tag_counts(&self, live_hashes: &HashSet<&str>) -> Vec<TagCount> {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        let live = self.annotations
+            .iter()
+            .filter(|(hash, _)| live_hashes.contains(hash.as_str()))
+            .map(|(_, stored)| &stored.annotation);
+        for annotation in live {
+            for tag in &annotation.tags {
+                *counts.entry(tag).or_default() += 1;
+            }
+        }
+
+        let mut tags: Vec<TagCount> = counts
+            .into_iter()
+            .map(|(tag, count)| TagCount { tag: tag.to_string(), count })
+            .collect();
+        tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+        tags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_merges_and_clears() {
+        let mut store = AnnotationStore {
+            path: PathBuf::new(),
+            annotations: HashMap::new(),
+        };
+
+        store.update("abc", (1, 10), &AnnotationUpdate {
+            add_tags: vec![" Hero ".to_string(), "client-a".to_string()],
+            rating: Some(4),
+            ..Default::default()
+        });
+        store.update("abc", (1, 10), &AnnotationUpdate {
+            add_tags: vec!["hero".to_string()],
+            favorite: Some(true),
+            ..Default::default()
+        });
+
+        let annotation = store.get("abc").unwrap();
+        assert_eq!(annotation.tags, vec!["client-a", "hero"]);
+        assert_eq!(annotation.rating, Some(4));
+        assert!(annotation.favorite);
+        assert!(AnnotationUpdate { rating: Some(6), ..Default::default() }.validate().is_err());
+        assert!(store.may_be_annotated((1, 10)));
+
+        // Only hashes of images still in the catalog are counted
+        assert_eq!(store.tag_counts(&HashSet::from(["abc"])).len(), 2);
+        assert!(store.tag_counts(&HashSet::new()).is_empty());
+
+        // Rewriting the file's bytes moves the annotation to the new hash
+        assert!(store.rekey("abc", "def", (2, 12)));
+        assert!(store.get("abc").is_none());
+        assert!(store.may_be_annotated((2, 12)));
+
+        // Clearing everything removes the entry
+        store.update("def", (2, 12), &AnnotationUpdate {
+            tags: Some(Vec::new()),
+            rating: Some(0),
+            favorite: Some(false),
+            ..Default::default()
+        });
+        assert!(store.get("def").is_none());
+    }
+}
@@ -0,0 +1,1046 @@
+//! Persistent per-project image catalog
+//! Keeps an on-disk index of generated_images so listing doesn't rescan the folder
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::annotations::{Annotation, AnnotationStore, AnnotationUpdate, TagCount};
+use crate::embed;
+use crate::formats::{self, is_image_path};
+use crate::metadata::{self, ImageMetadata};
+use crate::properties::{self, ImageProperties};
+use crate::search::{self, SearchIndex, Snippet};
+use crate::similarity;
+
+/// Bump when the on-disk catalog layout changes; older files are rebuilt from scratch
+const CATALOG_VERSION: u32 = 5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageFile {
+    pub path: String,
+    pub filename: String,
+    /// Folder relative to generated_images, using '/' separators ("" for the top level)
+    pub folder: String,
+    pub timestamp: String,
+    /// Modification time in milliseconds since the epoch, for sorting and range filters
+    pub modified: i64,
+    /// MIME type detected from the file's contents
+    pub mime_type: String,
+    /// Companion JSON, or metadata recovered from embedded text chunks if there isn't one
+    pub metadata: Option<ImageMetadata>,
+    /// Dimensions and other properties read from the file header
+    pub properties: Option<ImageProperties>,
+    /// Tags, rating, favourite flag and notes added in the app
+    pub annotation: Option<Annotation>,
+}
+
+/// A single indexed image, keyed in the catalog by its path relative to generated_images
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CatalogEntry {
+    /// Image modification time in milliseconds since the epoch
+    modified: i64,
+    /// Image size in bytes
+    size: u64,
+    mime_type: String,
+    /// Companion JSON modification time, if the JSON existed when indexed
+    json_modified: Option<i64>,
+    metadata: Option<ImageMetadata>,
+    properties: Option<ImageProperties>,
+    /// MD5 of the file contents, computed on first use since it means reading the whole file
+    #[serde(default)]
+    content_hash: Option<String>,
+    /// Difference hash of the pixels, for spotting near-duplicates. Also computed on first use.
+    #[serde(default)]
+    perceptual_hash: Option<u64>,
+}
+
+/// Serialized form of the catalog on disk
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CatalogFile {
+    version: u32,
+    entries: HashMap<String, CatalogEntry>,
+}
+
+/// A folder inside generated_images and how many images it holds
+#[derive(Debug, Clone, Serialize)]
+pub struct FolderNode {
+    pub name: String,
+    /// Path relative to generated_images ("" for the root)
+    pub path: String,
+    /// Images directly in this folder
+    pub image_count: usize,
+    /// Images in this folder and all of its subfolders
+    pub total_count: usize,
+    pub children: Vec<FolderNode>,
+}
+
+impl FolderNode {
+    fn new(name: &str, path: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            path: path.to_string(),
+            image_count: 0,
+            total_count: 0,
+            children: Vec::new(),
+        }
+    }
+
+    /// Count an image in the folder at `parts` below this node, creating folders as needed
+    fn add_image(&mut self, parts: &[&str]) {
+        self.total_count += 1;
+
+        let (first, rest) = match parts.split_first() {
+            Some(split) => split,
+            None => {
+                self.image_count += 1;
+                return;
+            }
+        };
+
+        let index = match self.children.iter().position(|c| c.name == *first) {
+            Some(i) => i,
+            None => {
+                let path = if self.path.is_empty() {
+                    first.to_string()
+                } else {
+                    format!("{}/{}", self.path, first)
+                };
+                self.children.push(FolderNode::new(first, &path));
+                self.children.len() - 1
+            }
+        };
+        self.children[index].add_image(rest);
+    }
+
+    fn sort(&mut self) {
+        self.children.sort_by(|a, b| a.name.cmp(&b.name));
+        for child in &mut self.children {
+            child.sort();
+        }
+    }
+}
+
+/// Folder part of a catalog key
+fn folder_of(key: &str) -> &str {
+    key.rsplit_once('/').map(|(folder, _)| folder).unwrap_or("")
+}
+
+/// Recursively collect image files under `dir`.
+/// Hidden folders (like our own trash) are skipped.
+fn collect_images(dir: &Path, found: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+
+        if is_dir {
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if !hidden {
+                collect_images(&path, found);
+            }
+        } else if is_image_path(&path) {
+            found.push(path);
+        }
+    }
+}
+
+/// Get the project-local directory where GenImage Studio keeps its own state
+pub fn get_studio_dir(project_path: &str) -> PathBuf {
+    Path::new(project_path).join(".genimage-studio")
+}
+
+/// Get the generated_images directory for a project
+pub fn get_images_dir(project_path: &str) -> PathBuf {
+    Path::new(project_path).join("generated_images")
+}
+
+pub fn is_metadata_path(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().eq_ignore_ascii_case("json"))
+        .unwrap_or(false)
+}
+
+/// Find the image a companion JSON belongs to (they share a file stem)
+pub fn companion_image(json_path: &Path) -> Option<PathBuf> {
+    formats::IMAGE_EXTENSIONS
+        .iter()
+        .flat_map(|ext| [ext.to_string(), ext.to_uppercase()])
+        .map(|ext| json_path.with_extension(ext))
+        .find(|image| image.exists())
+}
+
+/// Convert a SystemTime into milliseconds since the epoch
+fn to_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+fn modified_millis(path: &Path) -> Option<i64> {
+    fs::metadata(path).and_then(|m| m.modified()).ok().map(to_millis)
+}
+
+/// Format a millisecond timestamp as RFC 3339 (second precision, like the original listing)
+fn format_timestamp(millis: i64) -> String {
+    chrono::DateTime::from_timestamp(millis / 1000, 0)
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_default()
+}
+
+/// Index a single image from disk, or None if it no longer exists
+/// or its contents aren't a format we support
+fn index_image(path: &Path) -> Option<CatalogEntry> {
+    let file_meta = fs::metadata(path).ok()?;
+    if !file_meta.is_file() {
+        return None;
+    }
+    let format = formats::detect(path)?;
+
+    let json_path = path.with_extension("json");
+    let json_modified = modified_millis(&json_path);
+    let properties = properties::read(path, format).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path.display(), e);
+        None
+    });
+
+    let metadata = json_modified
+        .and_then(|_| metadata::read(&json_path).ok())
+        .or_else(|| {
+            let text = properties.as_ref().map(|p| p.text.clone()).unwrap_or_default();
+            embed::read(path, format, &text).or_else(|| metadata::from_text_chunks(&text))
+        });
+
+    Some(CatalogEntry {
+        modified: file_meta.modified().map(to_millis).unwrap_or_default(),
+        size: file_meta.len(),
+        mime_type: format.mime_type().to_string(),
+        json_modified,
+        metadata,
+        properties,
+        content_hash: None,
+        perceptual_hash: None,
+    })
+}
+
+/// MD5 of a file's contents as lowercase hex
+pub fn hash_file(path: &Path) -> Result<String, String> {
+    use std::io::Read;
+
+    let mut file = fs::File::open(path)
+        .map_err(|e| format!("Failed to open image: {}", e))?;
+    let mut context = md5::Context::new();
+    let mut buf = [0u8; 64 * 1024];
+
+    loop {
+        let n = file.read(&mut buf)
+            .map_err(|e| format!("Failed to read image: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        context.consume(&buf[..n]);
+    }
+
+    Ok(format!("{:x}", context.compute()))
+}
+
+/// Newly computed (content, perceptual) hashes; None where a hash wasn't needed or failed
+type HashResult = (Option<String>, Option<u64>);
+
+/// An image whose hashes still need computing
+struct HashJob {
+    path: PathBuf,
+    /// (modified, size) the image was indexed with, to detect it changing meanwhile
+    indexed_as: (i64, u64),
+    content: bool,
+    perceptual: bool,
+}
+
+impl HashJob {
+    /// Compute the missing hashes; failures are logged and left for next time
+    fn run(&self) -> HashResult {
+        let content_hash = if self.content {
+            hash_file(&self.path).map_err(|e| eprintln!("{}", e)).ok()
+        } else {
+            None
+        };
+
+        let decodable = formats::detect(&self.path).is_some_and(|f| f.is_decodable());
+        let perceptual_hash = if self.perceptual && decodable {
+            similarity::perceptual_hash(&self.path).map_err(|e| eprintln!("{}", e)).ok()
+        } else {
+            None
+        };
+
+        (content_hash, perceptual_hash)
+    }
+}
+
+/// Run hash jobs across all cores; decoding every image in a large gallery takes a while
+fn run_hash_jobs(jobs: &[HashJob]) -> Vec<(&HashJob, HashResult)> {
+    if jobs.is_empty() {
+        return Vec::new();
+    }
+    let workers = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+    let chunk_size = jobs.len().div_ceil(workers);
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = jobs
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(|job| (job, job.run())).collect::<Vec<_>>()))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_default())
+            .collect()
+    })
+}
+
+/// Hashes used to find duplicate and near-duplicate images
+#[derive(Debug, Clone)]
+pub struct Fingerprint {
+    pub image: ImageFile,
+    pub content_hash: String,
+    /// None for formats we can't decode
+    pub perceptual_hash: Option<u64>,
+}
+
+/// A search result with highlighted excerpts of the matching fields
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub image: ImageFile,
+    pub score: f64,
+    pub snippets: Vec<Snippet>,
+}
+
+/// Text that gets indexed for full-text search
+fn searchable_text(metadata: &Option<ImageMetadata>) -> String {
+    metadata
+        .as_ref()
+        .map(|m| {
+            [m.prompt.as_deref(), m.description.as_deref()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .unwrap_or_default()
+}
+
+/// How long changes are held before catalog.json is rewritten, so a burst is saved once
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Entries remembered after removal, so a rename can pick its hashes back up
+const RECENTLY_REMOVED_LIMIT: usize = 256;
+
+/// Catalog for a single project's generated_images directory
+pub struct Catalog {
+    images_dir: PathBuf,
+    index_path: PathBuf,
+    entries: HashMap<String, CatalogEntry>,
+    search: SearchIndex,
+    annotations: AnnotationStore,
+    /// Hashes of removed entries by (modified, size); renames keep both
+    recently_removed: HashMap<(i64, u64), (Option<String>, Option<u64>)>,
+    /// Changed since the last save
+    dirty: bool,
+}
+
+impl Catalog {
+    /// Load the catalog for a project and reconcile it with what's on disk
+    pub fn open(project_path: &str) -> Self {
+        let index_path = get_studio_dir(project_path).join("catalog.json");
+
+        let entries = fs::read_to_string(&index_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<CatalogFile>(&content).ok())
+            .filter(|file| file.version == CATALOG_VERSION)
+            .map(|file| file.entries)
+            .unwrap_or_default();
+
+        // The search index is cheap to rebuild from the stored metadata, so it isn't persisted
+        let mut search = SearchIndex::new();
+        for (key, entry) in &entries {
+            search.insert(key, &searchable_text(&entry.metadata));
+        }
+
+        let mut catalog = Self {
+            images_dir: get_images_dir(project_path),
+            index_path,
+            entries,
+            search,
+            annotations: AnnotationStore::load(&get_studio_dir(project_path)),
+            recently_removed: HashMap::new(),
+            dirty: false,
+        };
+
+        if catalog.reconcile() {
+            if let Err(e) = catalog.save() {
+                eprintln!("{}", e);
+            }
+        }
+
+        catalog
+    }
+
+    /// Get the catalog key for a path inside generated_images
+    fn key_for(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.images_dir).ok()?;
+        let parts: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join("/"))
+        }
+    }
+
+    fn insert_entry(&mut self, key: String, mut entry: CatalogEntry) {
+        // Hashes only depend on the image bytes, so keep them across metadata
+        // edits and renames rather than reading the whole file again
+        let file_id = (entry.modified, entry.size);
+        let previous = self.entries
+            .get(&key)
+            .filter(|existing| (existing.modified, existing.size) == file_id)
+            .map(|existing| (existing.content_hash.clone(), existing.perceptual_hash))
+            .or_else(|| self.recently_removed.remove(&file_id));
+        if let Some((content_hash, perceptual_hash)) = previous {
+            entry.content_hash = entry.content_hash.or(content_hash);
+            entry.perceptual_hash = entry.perceptual_hash.or(perceptual_hash);
+        }
+
+        // Hashes are normally computed on first use, but an image that may carry an
+        // annotation is hashed now so its tags show up and filters see it straight away
+        if entry.content_hash.is_none() && self.annotations.may_be_annotated(file_id) {
+            entry.content_hash = hash_file(&self.images_dir.join(&key)).ok();
+        }
+        if let Some(hash) = &entry.content_hash {
+            if self.annotations.seen(hash, file_id) {
+                if let Err(e) = self.annotations.save() {
+                    eprintln!("{}", e);
+                }
+            }
+        }
+
+        self.search.insert(&key, &searchable_text(&entry.metadata));
+        self.entries.insert(key, entry);
+    }
+
+    fn remove_entry(&mut self, key: &str) -> bool {
+        self.search.remove(key);
+        let removed = match self.entries.remove(key) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        if removed.content_hash.is_some() || removed.perceptual_hash.is_some() {
+            if self.recently_removed.len() >= RECENTLY_REMOVED_LIMIT {
+                self.recently_removed.clear();
+            }
+            self.recently_removed.insert(
+                (removed.modified, removed.size),
+                (removed.content_hash, removed.perceptual_hash),
+            );
+        }
+        true
+    }
+
+    /// Bring the index in line with the directory tree.
+    /// Only images whose size or timestamps changed are re-read.
+    /// Returns true if anything changed.
+    pub fn reconcile(&mut self) -> bool {
+        let mut found = Vec::new();
+        collect_images(&self.images_dir, &mut found);
+
+        let mut seen = HashMap::new();
+        for path in found {
+            if let Some(key) = self.key_for(&path) {
+                seen.insert(key, path);
+            }
+        }
+
+        let removed: Vec<String> = self.entries
+            .keys()
+            .filter(|key| !seen.contains_key(*key))
+            .cloned()
+            .collect();
+        let mut changed = !removed.is_empty();
+        for key in removed {
+            self.remove_entry(&key);
+        }
+
+        for (key, path) in seen {
+            let file_meta = match fs::metadata(&path) {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+            let modified = file_meta.modified().map(to_millis).unwrap_or_default();
+            let json_modified = modified_millis(&path.with_extension("json"));
+
+            let up_to_date = self.entries.get(&key).is_some_and(|existing| {
+                existing.modified == modified
+                    && existing.size == file_meta.len()
+                    && existing.json_modified == json_modified
+            });
+
+            if !up_to_date {
+                match index_image(&path) {
+                    Some(indexed) => {
+                        self.insert_entry(key, indexed);
+                        changed = true;
+                    }
+                    None => {
+                        changed |= self.remove_entry(&key);
+                    }
+                }
+            }
+        }
+
+        changed
+    }
+
+    /// Re-index whatever a changed path refers to: an image, or the image owning a companion JSON.
+    /// Paths that no longer exist are dropped from the index.
+    /// Returns true if anything changed.
+    pub fn apply_change(&mut self, path: &Path) -> bool {
+        let image_paths: Vec<PathBuf> = if is_image_path(path) {
+            vec![path.to_path_buf()]
+        } else if is_metadata_path(path) {
+            companion_image(path).into_iter().collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut changed = false;
+        for image_path in image_paths {
+            let key = match self.key_for(&image_path) {
+                Some(k) => k,
+                None => continue,
+            };
+
+            match index_image(&image_path) {
+                Some(indexed) => {
+                    self.insert_entry(key, indexed);
+                    changed = true;
+                }
+                None => {
+                    changed |= self.remove_entry(&key);
+                }
+            }
+        }
+
+        changed
+    }
+
+    /// Write the index to disk, replacing the previous file atomically
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(parent) = self.index_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create catalog directory: {}", e))?;
+        }
+
+        let file = CatalogFile {
+            version: CATALOG_VERSION,
+            entries: self.entries.clone(),
+        };
+        let content = serde_json::to_string(&file)
+            .map_err(|e| format!("Failed to serialize catalog: {}", e))?;
+
+        let tmp_path = self.index_path.with_extension("json.tmp");
+        fs::write(&tmp_path, content)
+            .map_err(|e| format!("Failed to write catalog: {}", e))?;
+        fs::rename(&tmp_path, &self.index_path)
+            .map_err(|e| format!("Failed to replace catalog: {}", e))?;
+
+        Ok(())
+    }
+
+    /// Save if anything changed since the last flush
+    fn flush(&mut self) -> Result<(), String> {
+        if !self.dirty {
+            return Ok(());
+        }
+        self.dirty = false;
+        self.save()
+    }
+
+    fn image_file(&self, key: &str, entry: &CatalogEntry) -> ImageFile {
+        let path = self.images_dir.join(key);
+        ImageFile {
+            path: path.to_string_lossy().to_string(),
+            filename: path.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            folder: folder_of(key).to_string(),
+            timestamp: format_timestamp(entry.modified),
+            modified: entry.modified,
+            mime_type: entry.mime_type.clone(),
+            metadata: entry.metadata.clone(),
+            properties: entry.properties.clone(),
+            annotation: entry.content_hash
+                .as_deref()
+                .and_then(|hash| self.annotations.get(hash))
+                .cloned(),
+        }
+    }
+
+    /// Cached content hash for an image, if the file is still as it was indexed.
+    /// Otherwise the error carries the file's (modified, size) as it is now, so the
+    /// new hash is only stored once the entry has caught up with the same file.
+    fn cached_hash(&self, path: &Path) -> Option<Result<String, (i64, u64)>> {
+        let key = self.key_for(path)?;
+        let entry = self.entries.get(&key)?;
+        let indexed_as = (entry.modified, entry.size);
+        let on_disk = fs::metadata(path)
+            .map(|meta| (meta.modified().map(to_millis).unwrap_or_default(), meta.len()))
+            .unwrap_or(indexed_as);
+        Some(match &entry.content_hash {
+            Some(hash) if on_disk == indexed_as => Ok(hash.clone()),
+            _ => Err(on_disk),
+        })
+    }
+
+    /// Every content hash computed so far
+    fn content_hashes(&self) -> HashSet<String> {
+        self.entries.values().filter_map(|entry| entry.content_hash.clone()).collect()
+    }
+
+    /// Remember a content hash, unless the image changed while it was being computed
+    fn store_hash(&mut self, path: &Path, indexed_as: (i64, u64), hash: &str) -> bool {
+        let entry = match self.key_for(path).and_then(|key| self.entries.get_mut(&key)) {
+            Some(e) => e,
+            None => return false,
+        };
+        if (entry.modified, entry.size) != indexed_as {
+            return false;
+        }
+        entry.content_hash = Some(hash.to_string());
+        true
+    }
+
+    /// Images missing a content hash, or a perceptual hash if `perceptual` is set
+    fn hash_jobs(&self, perceptual: bool) -> Vec<HashJob> {
+        self.entries
+            .iter()
+            .filter_map(|(key, entry)| {
+                let job = HashJob {
+                    path: self.images_dir.join(key),
+                    indexed_as: (entry.modified, entry.size),
+                    content: entry.content_hash.is_none(),
+                    perceptual: perceptual && entry.perceptual_hash.is_none(),
+                };
+                (job.content || job.perceptual).then_some(job)
+            })
+            .collect()
+    }
+
+    /// Remember the results of a hash job, unless the image changed while it ran
+    fn store_hashes(&mut self, job: &HashJob, content_hash: Option<String>, perceptual_hash: Option<u64>) -> bool {
+        let entry = match self.key_for(&job.path).and_then(|key| self.entries.get_mut(&key)) {
+            Some(e) => e,
+            None => return false,
+        };
+        if (entry.modified, entry.size) != job.indexed_as {
+            return false;
+        }
+
+        let mut changed = false;
+        if let Some(hash) = content_hash {
+            entry.content_hash = Some(hash);
+            changed = true;
+        }
+        if let Some(hash) = perceptual_hash {
+            entry.perceptual_hash = Some(hash);
+            changed = true;
+        }
+        changed
+    }
+
+    /// Hashes of every image whose content hash is known
+    fn fingerprints(&self) -> Vec<Fingerprint> {
+        self.entries
+            .iter()
+            .filter_map(|(key, entry)| {
+                Some(Fingerprint {
+                    image: self.image_file(key, entry),
+                    content_hash: entry.content_hash.clone()?,
+                    perceptual_hash: entry.perceptual_hash,
+                })
+            })
+            .collect()
+    }
+
+    /// Annotate an image whose content hash is already known
+    fn annotate(&mut self, path: &Path, content_hash: &str, update: &AnnotationUpdate) {
+        let file_id = match self.key_for(path).and_then(|key| self.entries.get(&key)) {
+            Some(entry) => (entry.modified, entry.size),
+            None => return,
+        };
+        self.annotations.update(content_hash, file_id, update);
+    }
+
+    /// Move an image's annotation to its new content hash after its bytes were rewritten
+    fn rekey_annotation(&mut self, path: &Path, old_hash: &str, new_hash: &str) -> Result<(), String> {
+        let file_id = match self.key_for(path).and_then(|key| self.entries.get(&key)) {
+            Some(entry) => (entry.modified, entry.size),
+            None => return Ok(()),
+        };
+        if old_hash != new_hash && self.annotations.rekey(old_hash, new_hash, file_id) {
+            self.annotations.save()?;
+        }
+        Ok(())
+    }
+
+    /// Tag counts over the images currently in the catalog
+    fn tag_counts(&self) -> Vec<TagCount> {
+        let live: HashSet<&str> = self.entries
+            .values()
+            .filter_map(|entry| entry.content_hash.as_deref())
+            .collect();
+        self.annotations.tag_counts(&live)
+    }
+
+    /// Find an image by its content hash, if that hash has been computed
+    fn find_by_hash(&self, content_hash: &str) -> Option<ImageFile> {
+        self.entries
+            .iter()
+            .find(|(_, entry)| entry.content_hash.as_deref() == Some(content_hash))
+            .map(|(key, entry)| self.image_file(key, entry))
+    }
+
+    /// Look up a single indexed image by its full path
+    pub fn get(&self, path: &Path) -> Option<ImageFile> {
+        let key = self.key_for(path)?;
+        let entry = self.entries.get(&key)?;
+        Some(self.image_file(&key, entry))
+    }
+
+    /// All indexed images
+    pub fn images(&self) -> Vec<ImageFile> {
+        self.entries
+            .iter()
+            .map(|(key, entry)| self.image_file(key, entry))
+            .collect()
+    }
+
+    /// Folder tree under generated_images with image counts
+    pub fn folders(&self) -> FolderNode {
+        let mut root = FolderNode::new("generated_images", "");
+        for key in self.entries.keys() {
+            let folder = folder_of(key);
+            let parts: Vec<&str> = if folder.is_empty() {
+                Vec::new()
+            } else {
+                folder.split('/').collect()
+            };
+            root.add_image(&parts);
+        }
+        root.sort();
+        root
+    }
+
+    /// Full-text search over prompts and descriptions
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let terms = search::tokenize(query).into_iter().collect();
+
+        self.search
+            .search(query)
+            .into_iter()
+            .take(limit)
+            .filter_map(|(key, score)| {
+                let entry = self.entries.get(&key)?;
+                let meta = entry.metadata.as_ref();
+                let snippets = [
+                    ("prompt", meta.and_then(|m| m.prompt.as_deref())),
+                    ("description", meta.and_then(|m| m.description.as_deref())),
+                ]
+                .into_iter()
+                .filter_map(|(field, text)| search::snippet(field, text?, &terms))
+                .collect();
+
+                Some(SearchHit {
+                    image: self.image_file(&key, entry),
+                    score,
+                    snippets,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Catalogs for every project opened during this session, keyed by project path
+pub struct CatalogState {
+    catalogs: Arc<Mutex<HashMap<String, Catalog>>>,
+    /// Projects with a debounced save scheduled
+    pending_saves: Arc<Mutex<HashSet<String>>>,
+}
+
+impl CatalogState {
+    pub fn new() -> Self {
+        Self {
+            catalogs: Arc::new(Mutex::new(HashMap::new())),
+            pending_saves: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Mark a project's catalog changed and save it shortly, once for the whole burst
+    fn schedule_save(&self, project_path: &str) {
+        self.with_catalog(project_path, |catalog| catalog.dirty = true);
+        if !self.pending_saves.lock().insert(project_path.to_string()) {
+            return;
+        }
+
+        let catalogs = self.catalogs.clone();
+        let pending_saves = self.pending_saves.clone();
+        let project_path = project_path.to_string();
+        thread::spawn(move || {
+            thread::sleep(SAVE_DEBOUNCE);
+            // Off the pending list first, so changes made from here on schedule another save
+            pending_saves.lock().remove(&project_path);
+            if let Some(catalog) = catalogs.lock().get_mut(&project_path) {
+                if let Err(e) = catalog.flush() {
+                    eprintln!("{}", e);
+                }
+            }
+        });
+    }
+
+    /// Write out every catalog with unsaved changes, e.g. before quitting
+    pub fn flush_all(&self) {
+        for catalog in self.catalogs.lock().values_mut() {
+            if let Err(e) = catalog.flush() {
+                eprintln!("{}", e);
+            }
+        }
+    }
+
+    /// Load a project's catalog if it isn't yet. Opening reconciles the whole folder,
+    /// so it happens outside the lock rather than blocking every other project.
+    fn ensure_open(&self, project_path: &str) {
+        if self.catalogs.lock().contains_key(project_path) {
+            return;
+        }
+        let catalog = Catalog::open(project_path);
+        // Another caller may have opened it meanwhile; keep whichever got there first
+        self.catalogs.lock().entry(project_path.to_string()).or_insert(catalog);
+    }
+
+    /// Run a closure against a project's catalog, loading it on first use
+    fn with_catalog<R>(&self, project_path: &str, f: impl FnOnce(&mut Catalog) -> R) -> R {
+        self.ensure_open(project_path);
+        let mut guard = self.catalogs.lock();
+        let catalog = guard
+            .entry(project_path.to_string())
+            .or_insert_with(|| Catalog::open(project_path));
+        f(catalog)
+    }
+
+    /// Content hashes known for a project's images, e.g. to prune caches keyed by them
+    pub fn content_hashes(&self, project_path: &str) -> HashSet<String> {
+        self.with_catalog(project_path, |catalog| catalog.content_hashes())
+    }
+
+    /// List every image in a project from the index
+    pub fn list(&self, project_path: &str) -> Vec<ImageFile> {
+        self.with_catalog(project_path, |catalog| catalog.images())
+    }
+
+    /// Look up a single image in a project's catalog
+    pub fn get(&self, project_path: &str, path: &Path) -> Option<ImageFile> {
+        self.with_catalog(project_path, |catalog| catalog.get(path))
+    }
+
+    /// Find an image by content hash in the given projects, in order, then in any
+    /// other open project, e.g. after it was moved or copied to another project.
+    /// If no known hash matches, images in the given projects that haven't been
+    /// hashed yet are hashed and searched too. Returns the project with the image.
+    pub fn find_by_hash(&self, project_paths: &[String], content_hash: &str) -> Option<(String, ImageFile)> {
+        if let Some(found) = self.find_by_known_hash(project_paths, content_hash) {
+            return Some(found);
+        }
+        project_paths.iter().find_map(|project_path| {
+            self.fingerprints(project_path, false)
+                .map_err(|e| eprintln!("{}", e))
+                .ok()?
+                .into_iter()
+                .find(|fingerprint| fingerprint.content_hash == content_hash)
+                .map(|fingerprint| (project_path.clone(), fingerprint.image))
+        })
+    }
+
+    fn find_by_known_hash(&self, project_paths: &[String], content_hash: &str) -> Option<(String, ImageFile)> {
+        for project_path in project_paths {
+            self.ensure_open(project_path);
+        }
+        let mut guard = self.catalogs.lock();
+        for project_path in project_paths {
+            let catalog = guard
+                .entry(project_path.clone())
+                .or_insert_with(|| Catalog::open(project_path));
+            if let Some(image) = catalog.find_by_hash(content_hash) {
+                return Some((project_path.clone(), image));
+            }
+        }
+        guard
+            .iter()
+            .filter(|(project_path, _)| !project_paths.contains(project_path))
+            .find_map(|(project_path, catalog)| {
+                catalog.find_by_hash(content_hash).map(|image| (project_path.clone(), image))
+            })
+    }
+
+    /// Content hash of an indexed image, computing it on first use.
+    /// Hashing happens outside the catalog lock so other lookups aren't blocked,
+    /// and new hashes are saved in a batch rather than one catalog write each.
+    pub fn content_hash(&self, project_path: &str, path: &Path) -> Result<String, String> {
+        let indexed_as = match self.with_catalog(project_path, |catalog| catalog.cached_hash(path)) {
+            Some(Ok(hash)) => return Ok(hash),
+            Some(Err(indexed_as)) => indexed_as,
+            None => return Err(format!("Image not found in catalog: {}", path.display())),
+        };
+
+        let hash = hash_file(path)?;
+
+        if self.with_catalog(project_path, |catalog| catalog.store_hash(path, indexed_as, &hash)) {
+            self.schedule_save(project_path);
+        }
+
+        Ok(hash)
+    }
+
+    /// Hashes of every image in a project, computing any that are missing.
+    /// Perceptual hashes mean decoding every image, so they're only computed when asked for.
+    pub fn fingerprints(&self, project_path: &str, perceptual: bool) -> Result<Vec<Fingerprint>, String> {
+        let jobs = self.with_catalog(project_path, |catalog| catalog.hash_jobs(perceptual));
+        let results = run_hash_jobs(&jobs);
+
+        self.with_catalog(project_path, |catalog| {
+            let mut changed = false;
+            for (job, (content_hash, perceptual_hash)) in results {
+                changed |= catalog.store_hashes(job, content_hash, perceptual_hash);
+            }
+            if changed {
+                catalog.save()?;
+            }
+            Ok(catalog.fingerprints())
+        })
+    }
+
+    /// Apply an annotation update to several images and persist it.
+    /// Returns the updated images.
+    pub fn annotate(&self, project_path: &str, paths: &[PathBuf], update: &AnnotationUpdate) -> Result<Vec<ImageFile>, String> {
+        update.validate()?;
+
+        // Hash first, since that may mean reading files we haven't hashed yet
+        let hashes = paths
+            .iter()
+            .map(|path| self.content_hash(project_path, path))
+            .collect::<Result<Vec<String>, String>>()?;
+
+        self.with_catalog(project_path, |catalog| {
+            for (path, hash) in paths.iter().zip(&hashes) {
+                catalog.annotate(path, hash, update);
+            }
+            catalog.annotations.save()?;
+            Ok(paths.iter().filter_map(|path| catalog.get(path)).collect())
+        })
+    }
+
+    /// Tags used in a project, most used first
+    pub fn tags(&self, project_path: &str) -> Vec<TagCount> {
+        self.with_catalog(project_path, |catalog| catalog.tag_counts())
+    }
+
+    /// Carry an image's annotation over after its file was rewritten in place,
+    /// e.g. by embedding metadata, which changes its content hash
+    pub fn rekey_annotation(&self, project_path: &str, path: &Path, old_hash: &str) -> Result<(), String> {
+        let new_hash = self.content_hash(project_path, path)?;
+        self.with_catalog(project_path, |catalog| catalog.rekey_annotation(path, old_hash, &new_hash))
+    }
+
+    /// Folder tree of a project's generated_images with image counts
+    pub fn folders(&self, project_path: &str) -> FolderNode {
+        self.with_catalog(project_path, |catalog| catalog.folders())
+    }
+
+    /// Search a project's prompts and descriptions
+    pub fn search(&self, project_path: &str, query: &str, limit: usize) -> Vec<SearchHit> {
+        self.with_catalog(project_path, |catalog| catalog.search(query, limit))
+    }
+
+    /// Rescan a project's directory and persist any differences
+    pub fn reconcile(&self, project_path: &str) -> Result<(), String> {
+        self.with_catalog(project_path, |catalog| {
+            if catalog.reconcile() {
+                catalog.save()
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    /// Apply a file system change reported by the watcher. The catalog is saved
+    /// shortly afterwards, once for a whole batch of changes.
+    pub fn apply_change(&self, project_path: &str, path: &Path) {
+        if self.with_catalog(project_path, |catalog| catalog.apply_change(path)) {
+            self.schedule_save(project_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconcile_tracks_nested_additions_and_deletions() {
+        let project = std::env::temp_dir().join(format!("catalog-test-{}", uuid::Uuid::new_v4()));
+        let project_path = project.to_string_lossy().to_string();
+        let images_dir = get_images_dir(&project_path);
+        fs::create_dir_all(&images_dir).unwrap();
+
+        fs::write(images_dir.join("a.png"), b"\x89PNG\r\n\x1a\n").unwrap();
+        fs::write(images_dir.join("a.json"), r#"{"prompt": "golden door"}"#).unwrap();
+        fs::write(images_dir.join("notes.txt"), b"ignored").unwrap();
+        fs::create_dir_all(images_dir.join("acme/spring")).unwrap();
+        fs::write(images_dir.join("acme/spring/b.webp"), b"RIFF\0\0\0\0WEBPVP8 ").unwrap();
+        fs::write(images_dir.join("fake.jpg"), b"<html>").unwrap();
+
+        let mut catalog = Catalog::open(&project_path);
+        let mut images = catalog.images();
+        images.sort_by(|a, b| a.filename.cmp(&b.filename));
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].filename, "a.png");
+        assert_eq!(images[1].folder, "acme/spring");
+        assert_eq!(images[1].mime_type, "image/webp");
+
+        let tree = catalog.folders();
+        assert_eq!((tree.image_count, tree.total_count), (1, 2));
+        assert_eq!(tree.children[0].children[0].path, "acme/spring");
+        assert_eq!(
+            images[0].metadata.as_ref().and_then(|m| m.prompt.as_deref()),
+            Some("golden door")
+        );
+
+        // The index persists across reopen
+        assert!(get_studio_dir(&project_path).join("catalog.json").exists());
+        assert_eq!(Catalog::open(&project_path).images().len(), 2);
+
+        fs::remove_file(images_dir.join("a.png")).unwrap();
+        assert!(catalog.apply_change(&images_dir.join("a.png")));
+        assert_eq!(catalog.images().len(), 1);
+
+        fs::remove_dir_all(&project).unwrap();
+    }
+}
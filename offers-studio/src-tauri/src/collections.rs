@@ -0,0 +1,376 @@
+//! Named collections of images, for mood boards and client deliverables
+//! Collections can mix images from any project, so they live in the app data dir
+//! rather than in a project. Items remember their content hash so they can be
+//! found again after the image is moved or renamed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use crate::catalog::{CatalogState, ImageFile};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionItem {
+    pub project_path: String,
+    pub path: String,
+    /// Used to find the image again if it moves
+    pub content_hash: Option<String>,
+    pub added_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collection {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+    pub updated_at: String,
+    /// In display order
+    pub items: Vec<CollectionItem>,
+}
+
+/// A collection item with its current catalog entry
+#[derive(Debug, Serialize)]
+pub struct ResolvedItem {
+    #[serde(flatten)]
+    pub item: CollectionItem,
+    /// None if the image is gone and couldn't be found by its content hash
+    pub image: Option<ImageFile>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResolvedCollection {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub items: Vec<ResolvedItem>,
+}
+
+fn now() -> String {
+    chrono::Local::now().to_rfc3339()
+}
+
+/// Get the file collections are stored in
+fn get_collections_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join("collections.json"))
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+/// Every collection, loaded from disk on first use
+pub struct CollectionState {
+    collections: Arc<Mutex<Option<Vec<Collection>>>>,
+}
+
+impl CollectionState {
+    pub fn new() -> Self {
+        Self {
+            collections: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Run a closure against the collections, saving afterwards if it succeeded and `save` is set
+    fn with_collections<R>(
+        &self,
+        app: &AppHandle,
+        save: bool,
+        f: impl FnOnce(&mut Vec<Collection>) -> Result<R, String>,
+    ) -> Result<R, String> {
+        let path = get_collections_path(app)?;
+        let mut guard = self.collections.lock();
+        let collections = guard.get_or_insert_with(|| {
+            fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str(&content).ok())
+                .unwrap_or_default()
+        });
+
+        let result = f(collections)?;
+        if save {
+            write_collections(&path, collections)?;
+        }
+        Ok(result)
+    }
+
+    pub fn list(&self, app: &AppHandle) -> Result<Vec<Collection>, String> {
+        self.with_collections(app, false, |collections| Ok(collections.clone()))
+    }
+
+    pub fn create(&self, app: &AppHandle, name: &str) -> Result<Collection, String> {
+        let name = validate_name(name)?;
+        self.with_collections(app, true, |collections| {
+            let collection = Collection {
+                id: uuid::Uuid::new_v4().to_string(),
+                name,
+                created_at: now(),
+                updated_at: now(),
+                items: Vec::new(),
+            };
+            collections.push(collection.clone());
+            Ok(collection)
+        })
+    }
+
+    pub fn rename(&self, app: &AppHandle, id: &str, name: &str) -> Result<(), String> {
+        let name = validate_name(name)?;
+        self.with_collections(app, true, |collections| {
+            let collection = find(collections, id)?;
+            collection.name = name;
+            collection.updated_at = now();
+            Ok(())
+        })
+    }
+
+    pub fn delete(&self, app: &AppHandle, id: &str) -> Result<(), String> {
+        self.with_collections(app, true, |collections| {
+            let before = collections.len();
+            collections.retain(|c| c.id != id);
+            if collections.len() == before {
+                return Err(format!("Collection not found: {}", id));
+            }
+            Ok(())
+        })
+    }
+
+    /// Put collections in the given order; any not listed keep their relative order at the end
+    pub fn reorder(&self, app: &AppHandle, ids: &[String]) -> Result<(), String> {
+        self.with_collections(app, true, |collections| {
+            collections.sort_by_key(|c| ids.iter().position(|id| *id == c.id).unwrap_or(usize::MAX));
+            Ok(())
+        })
+    }
+
+    /// Add images to a collection, skipping any already in it. Every image must be
+    /// in the project's catalog, since its content hash is what finds it after a move.
+    pub fn add(
+        &self,
+        app: &AppHandle,
+        catalog: &CatalogState,
+        id: &str,
+        project_path: &str,
+        paths: &[String],
+    ) -> Result<(), String> {
+        // Hash before taking the lock, since it may mean reading whole files
+        let items = paths
+            .iter()
+            .map(|path| {
+                Ok(CollectionItem {
+                    project_path: project_path.to_string(),
+                    path: path.clone(),
+                    content_hash: Some(catalog.content_hash(project_path, Path::new(path))?),
+                    added_at: now(),
+                })
+            })
+            .collect::<Result<Vec<CollectionItem>, String>>()?;
+
+        self.with_collections(app, true, |collections| {
+            find(collections, id)?.add(items);
+            Ok(())
+        })
+    }
+
+    pub fn remove(&self, app: &AppHandle, id: &str, paths: &[String]) -> Result<(), String> {
+        self.with_collections(app, true, |collections| {
+            find(collections, id)?.remove(paths);
+            Ok(())
+        })
+    }
+
+    /// Put a collection's items in the given order; any not listed go at the end
+    pub fn reorder_items(&self, app: &AppHandle, id: &str, paths: &[String]) -> Result<(), String> {
+        self.with_collections(app, true, |collections| {
+            find(collections, id)?.reorder(paths);
+            Ok(())
+        })
+    }
+
+    /// A collection with each item looked up in its project's catalog.
+    /// Items whose file has moved are found by content hash and their location updated.
+    pub fn resolve(&self, app: &AppHandle, catalog: &CatalogState, id: &str) -> Result<ResolvedCollection, String> {
+        let mut collection = self.with_collections(app, false, |collections| Ok(find(collections, id)?.clone()))?;
+        let projects = self.with_collections(app, false, |collections| Ok(known_projects(collections)))?;
+
+        let (images, moved) = collection.locate(catalog, &projects);
+        if !moved.is_empty() {
+            self.with_collections(app, true, |collections| {
+                let collection = find(collections, id)?;
+                for item in &mut collection.items {
+                    if let Some((_, to)) = moved.iter().find(|((project_path, path), _)| *project_path == item.project_path && *path == item.path) {
+                        (item.project_path, item.path) = to.clone();
+                    }
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(ResolvedCollection {
+            id: collection.id,
+            name: collection.name,
+            created_at: collection.created_at,
+            updated_at: collection.updated_at,
+            items: collection
+                .items
+                .into_iter()
+                .zip(images)
+                .map(|(item, image)| ResolvedItem { item, image })
+                .collect(),
+        })
+    }
+}
+
+/// (project, path) of an item before and after it moved
+type Move = ((String, String), (String, String));
+
+impl Collection {
+    /// Append items, skipping any already in the collection
+    fn add(&mut self, items: Vec<CollectionItem>) {
+        for item in items {
+            if !self.items.iter().any(|existing| existing.path == item.path) {
+                self.items.push(item);
+            }
+        }
+        self.updated_at = now();
+    }
+
+    fn remove(&mut self, paths: &[String]) {
+        self.items.retain(|item| !paths.contains(&item.path));
+        self.updated_at = now();
+    }
+
+    fn reorder(&mut self, paths: &[String]) {
+        self.items.sort_by_key(|item| paths.iter().position(|p| *p == item.path).unwrap_or(usize::MAX));
+        self.updated_at = now();
+    }
+
+    /// Look up each item's image, falling back to its content hash in the item's own
+    /// project, then the other `projects` and any open one. Items found elsewhere are
+    /// updated in place and returned as moves so the stored copy can follow.
+    fn locate(&mut self, catalog: &CatalogState, projects: &[String]) -> (Vec<Option<ImageFile>>, Vec<Move>) {
+        let mut moved = Vec::new();
+        let images = self
+            .items
+            .iter_mut()
+            .map(|item| {
+                if let Some(image) = catalog.get(&item.project_path, Path::new(&item.path)) {
+                    return Some(image);
+                }
+                let hash = item.content_hash.as_deref()?;
+                let search: Vec<String> = std::iter::once(item.project_path.clone())
+                    .chain(projects.iter().filter(|p| **p != item.project_path).cloned())
+                    .collect();
+                let (project_path, image) = catalog.find_by_hash(&search, hash)?;
+
+                let from = (item.project_path.clone(), item.path.clone());
+                (item.project_path, item.path) = (project_path, image.path.clone());
+                moved.push((from, (item.project_path.clone(), item.path.clone())));
+                Some(image)
+            })
+            .collect();
+        (images, moved)
+    }
+}
+
+/// Every project any collection has drawn images from
+fn known_projects(collections: &[Collection]) -> Vec<String> {
+    let mut projects: Vec<String> = Vec::new();
+    for item in collections.iter().flat_map(|c| &c.items) {
+        if !projects.contains(&item.project_path) {
+            projects.push(item.project_path.clone());
+        }
+    }
+    projects
+}
+
+fn validate_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Collection name can't be empty".to_string());
+    }
+    Ok(name.to_string())
+}
+
+fn find<'a>(collections: &'a mut [Collection], id: &str) -> Result<&'a mut Collection, String> {
+    collections
+        .iter_mut()
+        .find(|c| c.id == id)
+        .ok_or_else(|| format!("Collection not found: {}", id))
+}
+
+fn write_collections(path: &Path, collections: &[Collection]) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(collections)
+        .map_err(|e| format!("Failed to serialize collections: {}", e))?;
+
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, content)
+        .map_err(|e| format!("Failed to write collections: {}", e))?;
+    fs::rename(&tmp_path, path)
+        .map_err(|e| format!("Failed to replace collections: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::get_images_dir;
+
+    fn item(project_path: &str, path: &str, content_hash: Option<String>) -> CollectionItem {
+        CollectionItem {
+            project_path: project_path.to_string(),
+            path: path.to_string(),
+            content_hash,
+            added_at: now(),
+        }
+    }
+
+    #[test]
+    fn test_add_remove_reorder_and_resolve_after_move() {
+        let mut collection = Collection {
+            id: "c".to_string(),
+            name: "Board".to_string(),
+            created_at: now(),
+            updated_at: now(),
+            items: Vec::new(),
+        };
+        collection.add(vec![item("p", "/a.png", None), item("p", "/b.png", None)]);
+        collection.add(vec![item("p", "/b.png", None), item("p", "/c.png", None)]);
+        let paths = |c: &Collection| c.items.iter().map(|i| i.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&collection), ["/a.png", "/b.png", "/c.png"]);
+
+        collection.reorder(&["/c.png".to_string(), "/a.png".to_string()]);
+        assert_eq!(paths(&collection), ["/c.png", "/a.png", "/b.png"]);
+        collection.remove(&["/a.png".to_string()]);
+        assert_eq!(paths(&collection), ["/c.png", "/b.png"]);
+
+        // An image moved to another project is found there by its content hash
+        let root = std::env::temp_dir().join(format!("collections-test-{}", uuid::Uuid::new_v4()));
+        let (from, to) = (root.join("from"), root.join("to"));
+        let (from_path, to_path) = (from.to_string_lossy().to_string(), to.to_string_lossy().to_string());
+        let (from_images, to_images) = (get_images_dir(&from_path), get_images_dir(&to_path));
+        fs::create_dir_all(&from_images).unwrap();
+        fs::create_dir_all(&to_images).unwrap();
+        fs::write(from_images.join("d.png"), b"\x89PNG\r\n\x1a\nmoved").unwrap();
+
+        let catalog = CatalogState::new();
+        let original = from_images.join("d.png");
+        let hash = catalog.content_hash(&from_path, &original).unwrap();
+        let original = original.to_string_lossy().to_string();
+        collection.items = vec![item(&from_path, &original, Some(hash))];
+
+        fs::rename(from_images.join("d.png"), to_images.join("d.png")).unwrap();
+        catalog.reconcile(&from_path).unwrap();
+        let (images, moved) = collection.locate(&catalog, &[from_path.clone(), to_path.clone()]);
+
+        let found = images[0].as_ref().unwrap();
+        assert_eq!(Path::new(&found.path), to_images.join("d.png"));
+        assert_eq!(collection.items[0].project_path, to_path);
+        assert_eq!(moved, vec![((from_path, original), (to_path, found.path.clone()))]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,331 @@
+//! Comparison data for variations of the same prompt
+//! Lines up metadata across images, diffs prompts word by word against the first
+//! image and renders a pixel difference heat map for pairs of the same size
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use image::{Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use crate::catalog::{CatalogState, ImageFile};
+
+/// Channel difference above which a pixel counts as changed
+const CHANGED_THRESHOLD: u8 = 16;
+
+/// One metadata field across every compared image
+#[derive(Debug, Serialize)]
+pub struct FieldComparison {
+    pub field: &'static str,
+    /// One value per image, in the order they were given
+    pub values: Vec<Option<String>>,
+    pub differs: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Same,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DiffPart {
+    pub text: String,
+    pub change: Change,
+}
+
+/// Prompt of one image compared with the first image's prompt
+#[derive(Debug, Serialize)]
+pub struct PromptDiff {
+    /// Index of the image compared against the first
+    pub index: usize,
+    pub parts: Vec<DiffPart>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Heatmap {
+    /// Index of the image compared against the first
+    pub index: usize,
+    /// PNG where brighter means more different; black is identical
+    pub path: String,
+    /// Average per-pixel difference, 0 (identical) to 1
+    pub mean_difference: f64,
+    /// Share of pixels that changed noticeably
+    pub changed_ratio: f64,
+}
+
+/// Stats cached next to a heat map, so a cached pair isn't decoded again
+#[derive(Debug, Serialize, Deserialize)]
+struct HeatmapStats {
+    mean_difference: f64,
+    changed_ratio: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    pub images: Vec<ImageFile>,
+    pub fields: Vec<FieldComparison>,
+    pub prompt_diffs: Vec<PromptDiff>,
+    /// Only for images the same size as the first
+    pub heatmaps: Vec<Heatmap>,
+}
+
+fn compare_fields(images: &[ImageFile]) -> Vec<FieldComparison> {
+    type Getter = fn(&ImageFile) -> Option<String>;
+    let fields: [(&'static str, Getter); 6] = [
+        ("model", |i| i.metadata.as_ref()?.model.clone()),
+        ("aspect_ratio", |i| i.metadata.as_ref()?.aspect_ratio.clone()),
+        ("image_size", |i| i.metadata.as_ref()?.image_size.clone()),
+        ("dimensions", |i| i.properties.as_ref().map(|p| format!("{}×{}", p.width, p.height))),
+        ("mime_type", |i| Some(i.mime_type.clone())),
+        ("reference_images", |i| {
+            let references = &i.metadata.as_ref()?.reference_images;
+            (!references.is_empty()).then(|| references.join("\n"))
+        }),
+    ];
+
+    fields
+        .iter()
+        .map(|(field, get)| {
+            let values: Vec<Option<String>> = images.iter().map(get).collect();
+            let differs = values.windows(2).any(|pair| pair[0] != pair[1]);
+            FieldComparison { field, values, differs }
+        })
+        .collect()
+}
+
+/// Word-level diff of two texts (longest common subsequence)
+pub fn diff_words(old: &str, new: &str) -> Vec<DiffPart> {
+    let a: Vec<&str> = old.split_whitespace().collect();
+    let b: Vec<&str> = new.split_whitespace().collect();
+
+    // lcs[i][j] = length of the common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut parts: Vec<DiffPart> = Vec::new();
+    let mut push = |word: &str, change: Change| match parts.last_mut() {
+        Some(last) if last.change == change => {
+            last.text.push(' ');
+            last.text.push_str(word);
+        }
+        _ => parts.push(DiffPart { text: word.to_string(), change }),
+    };
+
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            push(a[i], Change::Same);
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            push(b[j], Change::Added);
+            j += 1;
+        } else {
+            push(a[i], Change::Removed);
+            i += 1;
+        }
+    }
+    parts
+}
+
+/// Map a 0-255 difference onto a black-red-yellow-white scale
+fn heat_color(value: u8) -> Rgb<u8> {
+    let v = value as u16 * 3;
+    Rgb([
+        v.min(255) as u8,
+        v.saturating_sub(255).min(255) as u8,
+        v.saturating_sub(510).min(255) as u8,
+    ])
+}
+
+/// Per-pixel difference image and its stats. Both images must be the same size.
+fn difference(a: &RgbImage, b: &RgbImage) -> (RgbImage, f64, f64) {
+    let mut total = 0u64;
+    let mut changed = 0u64;
+
+    let heatmap = RgbImage::from_fn(a.width(), a.height(), |x, y| {
+        let (pa, pb) = (a.get_pixel(x, y), b.get_pixel(x, y));
+        let diff = (0..3).map(|c| pa[c].abs_diff(pb[c])).max().unwrap_or(0);
+        total += diff as u64;
+        if diff > CHANGED_THRESHOLD {
+            changed += 1;
+        }
+        heat_color(diff)
+    });
+
+    let pixels = (a.width() as u64 * a.height() as u64).max(1) as f64;
+    (heatmap, total as f64 / pixels / 255.0, changed as f64 / pixels)
+}
+
+/// Get the directory heat maps are cached in
+fn get_cache_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_cache_dir()
+        .map(|dir| dir.join("compare"))
+        .map_err(|e| format!("Failed to get cache directory: {}", e))
+}
+
+fn decode_rgb(path: &Path) -> Result<RgbImage, String> {
+    image::open(path)
+        .map(|image| image.to_rgb8())
+        .map_err(|e| format!("Failed to decode {}: {}", path.display(), e))
+}
+
+fn read_stats(path: &Path) -> Option<HeatmapStats> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+/// Write through a uniquely named temp file so concurrent compares never see a partial file
+fn write_atomic(path: &Path, write: impl FnOnce(&Path) -> Result<(), String>) -> Result<(), String> {
+    let tmp_path = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    let result = write(&tmp_path).and_then(|()| {
+        fs::rename(&tmp_path, path)
+            .map_err(|e| format!("Failed to save heat map: {}", e))
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Render (or reuse) the heat map between two images, keyed by their content hashes.
+/// The base image is decoded into `base_pixels` the first time a pair isn't cached.
+fn heatmap(
+    cache_dir: &Path,
+    catalog: &CatalogState,
+    project_path: &str,
+    base: &ImageFile,
+    base_pixels: &mut Option<RgbImage>,
+    other: &ImageFile,
+    index: usize,
+) -> Result<Heatmap, String> {
+    let base_hash = catalog.content_hash(project_path, Path::new(&base.path))?;
+    let other_hash = catalog.content_hash(project_path, Path::new(&other.path))?;
+    let path = cache_dir.join(format!("{}-{}.png", base_hash, other_hash));
+    let stats_path = path.with_extension("json");
+
+    // The stats are written after the PNG, so having them means both are complete
+    let stats = match read_stats(&stats_path).filter(|_| path.exists()) {
+        Some(stats) => stats,
+        None => {
+            let base_pixels = match base_pixels {
+                Some(pixels) => pixels,
+                None => base_pixels.insert(decode_rgb(Path::new(&base.path))?),
+            };
+            let (map, mean_difference, changed_ratio) =
+                difference(base_pixels, &decode_rgb(Path::new(&other.path))?);
+            let stats = HeatmapStats { mean_difference, changed_ratio };
+
+            write_atomic(&path, |tmp_path| {
+                map.save_with_format(tmp_path, image::ImageFormat::Png)
+                    .map_err(|e| format!("Failed to save heat map: {}", e))
+            })?;
+            let content = serde_json::to_string(&stats)
+                .map_err(|e| format!("Failed to serialize heat map stats: {}", e))?;
+            write_atomic(&stats_path, |tmp_path| {
+                fs::write(tmp_path, content)
+                    .map_err(|e| format!("Failed to save heat map stats: {}", e))
+            })?;
+            stats
+        }
+    };
+
+    Ok(Heatmap {
+        index,
+        path: path.to_string_lossy().to_string(),
+        mean_difference: stats.mean_difference,
+        changed_ratio: stats.changed_ratio,
+    })
+}
+
+/// Compare two or more images, using the first as the baseline
+pub fn compare(
+    app: &AppHandle,
+    catalog: &CatalogState,
+    project_path: &str,
+    paths: &[String],
+) -> Result<Comparison, String> {
+    if paths.len() < 2 {
+        return Err("Pick at least two images to compare".to_string());
+    }
+
+    let images = paths
+        .iter()
+        .map(|path| {
+            catalog
+                .get(project_path, Path::new(path))
+                .ok_or_else(|| format!("Image not found in catalog: {}", path))
+        })
+        .collect::<Result<Vec<ImageFile>, String>>()?;
+
+    let prompt = |image: &ImageFile| image.metadata.as_ref().and_then(|m| m.prompt.clone()).unwrap_or_default();
+    let base = &images[0];
+    let base_prompt = prompt(base);
+
+    let prompt_diffs = images
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(index, image)| PromptDiff {
+            index,
+            parts: diff_words(&base_prompt, &prompt(image)),
+        })
+        .collect();
+
+    let cache_dir = &get_cache_dir(app)?;
+    fs::create_dir_all(cache_dir)
+        .map_err(|e| format!("Failed to create compare directory: {}", e))?;
+    let size = |image: &ImageFile| image.properties.as_ref().map(|p| (p.width, p.height));
+    let mut heatmaps = Vec::new();
+    let mut base_pixels = None;
+    for (index, image) in images.iter().enumerate().skip(1) {
+        if size(base).is_some() && size(base) == size(image) {
+            heatmaps.push(heatmap(cache_dir, catalog, project_path, base, &mut base_pixels, image, index)?);
+        }
+    }
+
+    Ok(Comparison {
+        fields: compare_fields(&images),
+        prompt_diffs,
+        heatmaps,
+        images,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_words_and_difference() {
+        let parts = diff_words("a golden door at dusk", "a red door at dawn");
+        let summary: Vec<(&str, Change)> = parts.iter().map(|p| (p.text.as_str(), p.change)).collect();
+        assert_eq!(summary, vec![
+            ("a", Change::Same),
+            ("red", Change::Added),
+            ("golden", Change::Removed),
+            ("door at", Change::Same),
+            ("dawn", Change::Added),
+            ("dusk", Change::Removed),
+        ]);
+
+        let a = RgbImage::from_pixel(4, 4, Rgb([10, 10, 10]));
+        let mut b = a.clone();
+        b.put_pixel(0, 0, Rgb([255, 10, 10]));
+        let (map, mean, changed) = difference(&a, &b);
+        assert_eq!(map.get_pixel(0, 0), &Rgb([255, 255, 225]));
+        assert_eq!(map.get_pixel(1, 1), &Rgb([0, 0, 0]));
+        assert!((changed - 1.0 / 16.0).abs() < 1e-9);
+        assert!(mean > 0.0);
+    }
+}
@@ -0,0 +1,290 @@
+//! Resizing and format conversion for deliverables
+//! Turns 4K PNGs into whatever a client asked for, with named presets stored per project
+
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use crate::catalog::{get_studio_dir, ImageFile};
+use crate::embed;
+use crate::formats::ImageFormat;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResizeMode {
+    /// Scale down to fit inside the box, keeping the aspect ratio
+    #[default]
+    Fit,
+    /// Scale to cover the box and crop the overflow from the centre
+    Crop,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    Jpeg,
+    /// Lossless only; the image crate has no lossy WebP encoder, so quality is rejected
+    Webp,
+}
+
+impl OutputFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Webp => "webp",
+        }
+    }
+
+    fn image_format(self) -> ImageFormat {
+        match self {
+            OutputFormat::Png => ImageFormat::Png,
+            OutputFormat::Jpeg => ImageFormat::Jpeg,
+            OutputFormat::Webp => ImageFormat::Webp,
+        }
+    }
+}
+
+/// JPEG quality when none is given
+const DEFAULT_QUALITY: u8 = 85;
+
+fn default_preserve() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversionSettings {
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    #[serde(default)]
+    pub mode: ResizeMode,
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// JPEG quality, 1-100. Not allowed for lossless formats.
+    #[serde(default)]
+    pub quality: Option<u8>,
+    /// Embed the generation metadata in the output (PNG and JPEG), or strip everything
+    #[serde(default = "default_preserve")]
+    pub preserve_metadata: bool,
+}
+
+impl ConversionSettings {
+    /// Reject settings that would be silently ignored
+    pub fn validate(&self) -> Result<(), String> {
+        match (self.format, self.quality) {
+            (OutputFormat::Png | OutputFormat::Webp, Some(_)) => Err(format!(
+                "Quality only applies to JPEG; {} output is lossless",
+                self.format.extension().to_uppercase()
+            )),
+            (OutputFormat::Jpeg, Some(quality)) if !(1..=100).contains(&quality) => {
+                Err(format!("JPEG quality must be 1-100, got {}", quality))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportPreset {
+    pub name: String,
+    pub settings: ConversionSettings,
+}
+
+fn presets_path(project_path: &str) -> PathBuf {
+    get_studio_dir(project_path).join("presets.json")
+}
+
+/// A project's saved presets, in the order they were created
+pub fn load_presets(project_path: &str) -> Vec<ExportPreset> {
+    fs::read_to_string(presets_path(project_path))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_presets(project_path: &str, presets: &[ExportPreset]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(presets)
+        .map_err(|e| format!("Failed to serialize presets: {}", e))?;
+    fs::create_dir_all(get_studio_dir(project_path))
+        .map_err(|e| format!("Failed to create studio directory: {}", e))?;
+
+    let path = presets_path(project_path);
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, content)
+        .map_err(|e| format!("Failed to write presets: {}", e))?;
+    fs::rename(&tmp_path, &path)
+        .map_err(|e| format!("Failed to replace presets: {}", e))
+}
+
+/// Add a preset, replacing any existing one with the same name
+pub fn save_preset(project_path: &str, preset: ExportPreset) -> Result<(), String> {
+    if preset.name.trim().is_empty() {
+        return Err("Preset name can't be empty".to_string());
+    }
+    preset.settings.validate()?;
+    let mut presets = load_presets(project_path);
+    match presets.iter_mut().find(|p| p.name == preset.name) {
+        Some(existing) => *existing = preset,
+        None => presets.push(preset),
+    }
+    save_presets(project_path, &presets)
+}
+
+/// Remove a preset by name. Returns false if there was no such preset.
+pub fn delete_preset(project_path: &str, name: &str) -> Result<bool, String> {
+    let mut presets = load_presets(project_path);
+    let before = presets.len();
+    presets.retain(|p| p.name != name);
+    if presets.len() == before {
+        return Ok(false);
+    }
+    save_presets(project_path, &presets)?;
+    Ok(true)
+}
+
+/// Settings given directly, or those of a named preset
+pub fn resolve_settings(
+    project_path: &str,
+    settings: Option<ConversionSettings>,
+    preset: Option<&str>,
+) -> Result<Option<ConversionSettings>, String> {
+    let settings = match (settings, preset) {
+        (Some(settings), _) => settings,
+        (None, Some(name)) => load_presets(project_path)
+            .into_iter()
+            .find(|p| p.name == name)
+            .map(|p| p.settings)
+            .ok_or_else(|| format!("No export preset named {}", name))?,
+        (None, None) => return Ok(None),
+    };
+    settings.validate()?;
+    Ok(Some(settings))
+}
+
+/// Apply the size limits. Images are never scaled up.
+fn resize(image: DynamicImage, settings: &ConversionSettings) -> DynamicImage {
+    let (width, height) = (image.width(), image.height());
+
+    match (settings.mode, settings.max_width, settings.max_height) {
+        (ResizeMode::Crop, Some(w), Some(h)) => {
+            let (w, h) = (w.min(width), h.min(height));
+            if (w, h) == (width, height) {
+                image
+            } else {
+                image.resize_to_fill(w, h, FilterType::Lanczos3)
+            }
+        }
+        (_, max_width, max_height) => {
+            let w = max_width.unwrap_or(width);
+            let h = max_height.unwrap_or(height);
+            if width <= w && height <= h {
+                image
+            } else {
+                image.resize(w, h, FilterType::Lanczos3)
+            }
+        }
+    }
+}
+
+fn encode(image: &DynamicImage, settings: &ConversionSettings) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let cursor = Cursor::new(&mut bytes);
+
+    let result = match settings.format {
+        OutputFormat::Png => image.write_with_encoder(PngEncoder::new(cursor)),
+        // No alpha channel in JPEG
+        OutputFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(cursor, settings.quality.unwrap_or(DEFAULT_QUALITY))),
+        OutputFormat::Webp => DynamicImage::ImageRgba8(image.to_rgba8())
+            .write_with_encoder(WebPEncoder::new_lossless(cursor)),
+    };
+    result.map_err(|e| format!("Failed to encode image: {}", e))?;
+
+    Ok(bytes)
+}
+
+/// Convert a gallery image, returning the encoded bytes
+pub fn convert(image: &ImageFile, settings: &ConversionSettings) -> Result<Vec<u8>, String> {
+    let decoded = image::ImageReader::open(&image.path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|e| format!("Failed to open image: {}", e))?
+        .decode()
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+
+    let bytes = encode(&resize(decoded, settings), settings)?;
+
+    match (&image.metadata, settings.preserve_metadata, settings.format) {
+        (Some(metadata), true, OutputFormat::Png | OutputFormat::Jpeg) => {
+            embed::embed(&bytes, settings.format.image_format(), metadata)
+        }
+        _ => Ok(bytes),
+    }
+}
+
+/// Name for a converted image: same stem, new extension. Images that differ only by
+/// extension end up with the same name, so the export makes names unique.
+pub fn output_name(name: &str, settings: &ConversionSettings) -> String {
+    Path::new(name)
+        .with_extension(settings.format.extension())
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(mode: ResizeMode, max_width: Option<u32>, max_height: Option<u32>) -> ConversionSettings {
+        ConversionSettings {
+            max_width,
+            max_height,
+            mode,
+            format: OutputFormat::Jpeg,
+            quality: Some(80),
+            preserve_metadata: false,
+        }
+    }
+
+    #[test]
+    fn test_resize_modes() {
+        let image = DynamicImage::new_rgb8(1600, 900);
+
+        let fit = resize(image.clone(), &settings(ResizeMode::Fit, Some(800), None));
+        assert_eq!((fit.width(), fit.height()), (800, 450));
+
+        let crop = resize(image.clone(), &settings(ResizeMode::Crop, Some(400), Some(400)));
+        assert_eq!((crop.width(), crop.height()), (400, 400));
+
+        // Never upscaled
+        let small = resize(DynamicImage::new_rgb8(800, 600), &settings(ResizeMode::Fit, Some(1200), None));
+        assert_eq!((small.width(), small.height()), (800, 600));
+
+        let bytes = encode(&crop, &settings(ResizeMode::Crop, None, None)).unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (400, 400));
+    }
+
+    #[test]
+    fn test_quality_only_for_jpeg() {
+        let mut webp = settings(ResizeMode::Fit, None, None);
+        webp.format = OutputFormat::Webp;
+        assert!(webp.validate().unwrap_err().contains("WEBP output is lossless"));
+
+        webp.quality = None;
+        assert!(webp.validate().is_ok());
+        let bytes = encode(&DynamicImage::new_rgb8(8, 8), &webp).unwrap();
+        assert_eq!(image::guess_format(&bytes).unwrap(), image::ImageFormat::WebP);
+
+        let mut jpeg = settings(ResizeMode::Fit, None, None);
+        assert!(jpeg.validate().is_ok());
+        jpeg.quality = Some(0);
+        assert!(jpeg.validate().is_err());
+    }
+}
@@ -0,0 +1,344 @@
+//! Generation metadata stored inside the image file
+//! PNGs carry it in an iTXt chunk and JPEGs in an XMP packet, so provenance
+//! survives the image being copied away from its companion JSON
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use crate::formats::ImageFormat;
+use crate::metadata::{self, ImageMetadata};
+
+/// iTXt keyword (and XMP property) the metadata JSON is stored under
+pub const METADATA_KEYWORD: &str = "genimage:metadata";
+
+const XMP_NAMESPACE: &str = "https://genimage.studio/ns/1.0/";
+
+/// Identifies an APP1 segment as XMP rather than EXIF
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+const PNG_SIGNATURE_LEN: usize = 8;
+
+/// Add metadata to an encoded image in memory, replacing anything we embedded before
+pub fn embed(bytes: &[u8], format: ImageFormat, metadata: &ImageMetadata) -> Result<Vec<u8>, String> {
+    let json = serde_json::to_string(metadata)
+        .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
+
+    match format {
+        ImageFormat::Png => embed_png(bytes, &json),
+        ImageFormat::Jpeg => embed_jpeg(bytes, &json),
+        other => Err(format!("Can't embed metadata in {}", other.mime_type())),
+    }
+}
+
+/// Write metadata into an image file, replacing anything we embedded before
+pub fn write(path: &Path, format: ImageFormat, metadata: &ImageMetadata) -> Result<(), String> {
+    let bytes = fs::read(path)
+        .map_err(|e| format!("Failed to read image: {}", e))?;
+    let embedded = embed(&bytes, format, metadata)?;
+
+    // Replace the original atomically so a failed write never corrupts the image
+    let tmp_path = path.with_extension("embed.tmp");
+    fs::write(&tmp_path, embedded)
+        .map_err(|e| format!("Failed to write image: {}", e))?;
+    fs::rename(&tmp_path, path)
+        .map_err(|e| format!("Failed to replace image: {}", e))
+}
+
+/// Read metadata we embedded earlier.
+/// PNG text chunks have already been read into the image's properties, so they're passed in.
+pub fn read(path: &Path, format: ImageFormat, text: &BTreeMap<String, String>) -> Option<ImageMetadata> {
+    let json = match format {
+        ImageFormat::Png => text.get(METADATA_KEYWORD).cloned(),
+        ImageFormat::Jpeg => read_jpeg_xmp(path),
+        _ => None,
+    }?;
+    metadata::parse(&json).ok()
+}
+
+/// Iterate over a PNG's chunks as (type, data, whole chunk including length and CRC)
+fn png_chunks(bytes: &[u8]) -> impl Iterator<Item = (&[u8], &[u8], &[u8])> {
+    let mut offset = PNG_SIGNATURE_LEN;
+    std::iter::from_fn(move || {
+        let header = bytes.get(offset..offset + 8)?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let end = offset + 12 + length;
+        let chunk = bytes.get(offset..end)?;
+        offset = end;
+        Some((&chunk[4..8], &chunk[8..8 + length], chunk))
+    })
+}
+
+fn png_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut crc = flate2::Crc::new();
+    crc.update(chunk_type);
+    crc.update(data);
+
+    let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(chunk_type);
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&crc.sum().to_be_bytes());
+    chunk
+}
+
+/// Whether a text chunk's keyword is ours
+fn is_metadata_chunk(chunk_type: &[u8], data: &[u8]) -> bool {
+    matches!(chunk_type, b"tEXt" | b"zTXt" | b"iTXt")
+        && data.starts_with(METADATA_KEYWORD.as_bytes())
+        && data.get(METADATA_KEYWORD.len()) == Some(&0)
+}
+
+/// Copy a PNG with an uncompressed iTXt metadata chunk inserted before the image data
+fn embed_png(bytes: &[u8], json: &str) -> Result<Vec<u8>, String> {
+    // keyword \0 compression-flag method language \0 translated-keyword \0 text
+    let mut itxt = METADATA_KEYWORD.as_bytes().to_vec();
+    itxt.extend_from_slice(&[0, 0, 0, 0, 0]);
+    itxt.extend_from_slice(json.as_bytes());
+    let metadata_chunk = png_chunk(b"iTXt", &itxt);
+
+    let mut output = bytes[..PNG_SIGNATURE_LEN.min(bytes.len())].to_vec();
+    let mut inserted = false;
+    for (chunk_type, data, chunk) in png_chunks(bytes) {
+        if is_metadata_chunk(chunk_type, data) {
+            continue;
+        }
+        if chunk_type == b"IDAT" && !inserted {
+            output.extend_from_slice(&metadata_chunk);
+            inserted = true;
+        }
+        output.extend_from_slice(chunk);
+    }
+
+    if !inserted {
+        return Err("PNG has no image data".to_string());
+    }
+    Ok(output)
+}
+
+/// A JPEG marker segment before the image data: (marker, payload, whole segment)
+struct JpegSegment<'a> {
+    marker: u8,
+    payload: &'a [u8],
+    bytes: &'a [u8],
+}
+
+/// Split a JPEG into its header segments and everything from the start of scan onwards
+fn jpeg_segments(bytes: &[u8]) -> Result<(Vec<JpegSegment<'_>>, &[u8]), String> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return Err("Not a JPEG file".to_string());
+    }
+
+    let mut segments = Vec::new();
+    let mut offset = 2;
+    loop {
+        if bytes.get(offset) != Some(&0xFF) {
+            return Err("Malformed JPEG".to_string());
+        }
+        let marker = *bytes.get(offset + 1).ok_or("Truncated JPEG")?;
+        match marker {
+            // Fill byte
+            0xFF => offset += 1,
+            // Start of scan: the rest is entropy-coded data we copy as is
+            0xDA => return Ok((segments, &bytes[offset..])),
+            _ => {
+                let length = bytes.get(offset + 2..offset + 4).ok_or("Truncated JPEG")?;
+                let length = u16::from_be_bytes([length[0], length[1]]) as usize;
+                let end = offset + 2 + length;
+                let segment = bytes.get(offset..end).ok_or("Truncated JPEG")?;
+                segments.push(JpegSegment {
+                    marker,
+                    payload: &segment[4..],
+                    bytes: segment,
+                });
+                offset = end;
+            }
+        }
+    }
+}
+
+fn is_xmp_segment(segment: &JpegSegment) -> bool {
+    segment.marker == 0xE1 && segment.payload.starts_with(XMP_HEADER)
+}
+
+/// An XMP packet holding our metadata, as opposed to one written by another tool
+fn is_our_xmp_segment(segment: &JpegSegment) -> bool {
+    let attribute = format!("{}=\"", METADATA_KEYWORD);
+    is_xmp_segment(segment)
+        && segment.payload.windows(attribute.len()).any(|window| window == attribute.as_bytes())
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\r' => escaped.push_str("&#13;"),
+            '\t' => escaped.push_str("&#9;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape_xml(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        let entity_end = match rest[start..].find(';') {
+            Some(end) => start + end,
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        };
+        let entity = &rest[start + 1..entity_end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse()))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => unescaped.push(c),
+            None => unescaped.push_str(&rest[start..=entity_end]),
+        }
+        rest = &rest[entity_end + 1..];
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+fn xmp_packet(json: &str) -> String {
+    format!(
+        concat!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>",
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">",
+            "<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">",
+            "<rdf:Description rdf:about=\"\" xmlns:genimage=\"{}\" {}=\"{}\"/>",
+            "</rdf:RDF>",
+            "</x:xmpmeta>",
+            "<?xpacket end=\"w\"?>"
+        ),
+        XMP_NAMESPACE,
+        METADATA_KEYWORD,
+        escape_xml(json)
+    )
+}
+
+/// Copy a JPEG with our XMP packet replaced by one holding the metadata.
+/// XMP from other tools is kept as it is. The packet goes after any JFIF/EXIF
+/// segments, which readers expect first.
+fn embed_jpeg(bytes: &[u8], json: &str) -> Result<Vec<u8>, String> {
+    let mut payload = XMP_HEADER.to_vec();
+    payload.extend_from_slice(xmp_packet(json).as_bytes());
+    // The length field covers itself and is only 16 bits
+    let length = u16::try_from(payload.len() + 2)
+        .map_err(|_| "Metadata is too large to embed in a JPEG".to_string())?;
+
+    let (segments, scan) = jpeg_segments(bytes)?;
+    let insert_at = segments
+        .iter()
+        .take_while(|s| s.marker == 0xE0 || (s.marker == 0xE1 && !is_xmp_segment(s)))
+        .count();
+
+    let mut output = vec![0xFF, 0xD8];
+    for (i, segment) in segments.iter().enumerate() {
+        if i == insert_at {
+            output.extend_from_slice(&[0xFF, 0xE1]);
+            output.extend_from_slice(&length.to_be_bytes());
+            output.extend_from_slice(&payload);
+        }
+        if !is_our_xmp_segment(segment) {
+            output.extend_from_slice(segment.bytes);
+        }
+    }
+    if insert_at == segments.len() {
+        output.extend_from_slice(&[0xFF, 0xE1]);
+        output.extend_from_slice(&length.to_be_bytes());
+        output.extend_from_slice(&payload);
+    }
+    output.extend_from_slice(scan);
+    Ok(output)
+}
+
+/// Find our metadata JSON in a JPEG's XMP packet
+fn read_jpeg_xmp(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    let (segments, _) = jpeg_segments(&bytes).ok()?;
+    let segment = segments.iter().find(|s| is_our_xmp_segment(s))?;
+    let packet = String::from_utf8_lossy(&segment.payload[XMP_HEADER.len()..]);
+
+    let attribute = format!("{}=\"", METADATA_KEYWORD);
+    let start = packet.find(&attribute)? + attribute.len();
+    let end = start + packet[start..].find('"')?;
+    Some(unescape_xml(&packet[start..end]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats;
+    use crate::properties;
+
+    #[test]
+    fn test_embedded_metadata_round_trips() {
+        let dir = std::env::temp_dir().join(format!("embed-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let metadata = ImageMetadata {
+            prompt: Some("A \"golden\" door <at dusk> & rain\nwide shot".to_string()),
+            model: Some("gemini-2.5-flash-image".to_string()),
+            reference_images: vec!["refs/door.png".to_string()],
+            ..metadata::parse("{}").unwrap()
+        };
+
+        for name in ["image.png", "image.jpg"] {
+            let path = dir.join(name);
+            image::RgbImage::new(4, 4).save(&path).unwrap();
+            let format = formats::detect(&path).unwrap();
+
+            // Embedding again replaces what was there
+            write(&path, format, &ImageMetadata::default()).unwrap();
+            write(&path, format, &metadata).unwrap();
+
+            let properties = properties::read(&path, format).unwrap().unwrap();
+            assert_eq!((properties.width, properties.height), (4, 4), "{} still decodes", name);
+
+            let read_back = read(&path, format, &properties.text).unwrap();
+            assert_eq!(read_back.prompt, metadata.prompt, "{}", name);
+            assert_eq!(read_back.reference_images, metadata.reference_images, "{}", name);
+            image::open(&path).unwrap();
+        }
+
+        // XMP from another tool survives embedding, ours is replaced
+        let mut jpeg = Vec::new();
+        image::RgbImage::new(4, 4)
+            .write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+            .unwrap();
+        let foreign = [XMP_HEADER, b"<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"/>"].concat();
+        let mut segment = vec![0xFF, 0xE1];
+        segment.extend_from_slice(&((foreign.len() + 2) as u16).to_be_bytes());
+        segment.extend_from_slice(&foreign);
+        jpeg.splice(2..2, segment);
+
+        let embedded = embed_jpeg(&embed_jpeg(&jpeg, "{}").unwrap(), "{\"prompt\":\"door\"}").unwrap();
+        let (segments, _) = jpeg_segments(&embedded).unwrap();
+        let xmp: Vec<&JpegSegment> = segments.iter().filter(|s| is_xmp_segment(s)).collect();
+        assert_eq!(xmp.len(), 2);
+        assert_eq!(xmp.iter().filter(|s| is_our_xmp_segment(s)).count(), 1);
+        assert!(xmp.iter().any(|s| s.payload == foreign.as_slice()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,436 @@
+//! Bulk export of images for client hand-off
+//! Writes the images, their companion JSON and a manifest of prompts and models
+//! to a ZIP or a folder, keeping the generated_images folder layout
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+use zip::write::SimpleFileOptions;
+use zip::CompressionMethod;
+use crate::catalog::{CatalogState, ImageFile};
+use crate::convert::{self, ConversionSettings};
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Zip,
+    Folder,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Include each image's companion JSON alongside it
+    pub include_metadata: bool,
+    /// Write manifest.json and manifest.csv
+    pub manifest: bool,
+    /// Convert images rather than copying the originals
+    pub conversion: Option<ConversionSettings>,
+    /// Name of a saved preset to convert with, if `conversion` isn't given
+    pub preset: Option<String>,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            format: ExportFormat::Zip,
+            include_metadata: true,
+            manifest: true,
+            conversion: None,
+            preset: None,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct ExportProgress {
+    /// Identifies the export, since the frontend knows where it asked for it to go
+    destination: String,
+    completed: usize,
+    total: usize,
+    /// Image just written, relative to the export root
+    current: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportSummary {
+    pub destination: String,
+    pub image_count: usize,
+    /// Bytes of image and metadata data written, before compression
+    pub total_bytes: u64,
+    /// Written image names, relative to the destination
+    pub files: Vec<String>,
+}
+
+/// One row of the manifest
+#[derive(Serialize)]
+struct ManifestEntry {
+    file: String,
+    prompt: Option<String>,
+    model: Option<String>,
+    aspect_ratio: Option<String>,
+    image_size: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    timestamp: Option<String>,
+    reference_images: Vec<String>,
+}
+
+impl ManifestEntry {
+    fn new(file: &str, image: &ImageFile) -> Self {
+        let meta = image.metadata.clone().unwrap_or_default();
+        Self {
+            file: file.to_string(),
+            prompt: meta.prompt,
+            model: meta.model,
+            aspect_ratio: meta.aspect_ratio,
+            image_size: meta.image_size,
+            width: image.properties.as_ref().map(|p| p.width),
+            height: image.properties.as_ref().map(|p| p.height),
+            timestamp: meta.timestamp,
+            reference_images: meta.reference_images,
+        }
+    }
+}
+
+/// Quote a CSV field if it needs it (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn manifest_csv(entries: &[ManifestEntry]) -> String {
+    let mut csv = String::from("file,prompt,model,aspect_ratio,image_size,width,height,timestamp,reference_images\r\n");
+    for entry in entries {
+        let fields = [
+            entry.file.clone(),
+            entry.prompt.clone().unwrap_or_default(),
+            entry.model.clone().unwrap_or_default(),
+            entry.aspect_ratio.clone().unwrap_or_default(),
+            entry.image_size.clone().unwrap_or_default(),
+            entry.width.map(|w| w.to_string()).unwrap_or_default(),
+            entry.height.map(|h| h.to_string()).unwrap_or_default(),
+            entry.timestamp.clone().unwrap_or_default(),
+            entry.reference_images.join(";"),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+/// Where exported files end up
+trait ExportSink {
+    fn add_file(&mut self, name: &str, source: &Path, compress: bool) -> Result<(), String>;
+    fn add_bytes(&mut self, name: &str, bytes: &[u8], compress: bool) -> Result<(), String>;
+    fn finish(self: Box<Self>) -> Result<(), String>;
+}
+
+struct FolderSink {
+    root: PathBuf,
+}
+
+impl FolderSink {
+    fn target(&self, name: &str) -> Result<PathBuf, String> {
+        let target = self.root.join(name);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create export folder: {}", e))?;
+        }
+        Ok(target)
+    }
+}
+
+impl ExportSink for FolderSink {
+    fn add_file(&mut self, name: &str, source: &Path, _compress: bool) -> Result<(), String> {
+        fs::copy(source, self.target(name)?)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to copy {}: {}", name, e))
+    }
+
+    fn add_bytes(&mut self, name: &str, bytes: &[u8], _compress: bool) -> Result<(), String> {
+        fs::write(self.target(name)?, bytes)
+            .map_err(|e| format!("Failed to write {}: {}", name, e))
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Writes to a temp file that replaces the destination once the archive is complete
+struct ZipSink {
+    writer: zip::ZipWriter<io::BufWriter<fs::File>>,
+    tmp_path: PathBuf,
+    destination: PathBuf,
+}
+
+impl ZipSink {
+    fn options(compress: bool, size: u64) -> SimpleFileOptions {
+        // Images are already compressed, so deflating them only costs time
+        let method = if compress { CompressionMethod::Deflated } else { CompressionMethod::Stored };
+        SimpleFileOptions::default()
+            .compression_method(method)
+            .large_file(size > u32::MAX as u64)
+    }
+}
+
+impl ExportSink for ZipSink {
+    fn add_file(&mut self, name: &str, source: &Path, compress: bool) -> Result<(), String> {
+        let mut file = fs::File::open(source)
+            .map_err(|e| format!("Failed to open {}: {}", source.display(), e))?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or_default();
+        self.writer.start_file(name, Self::options(compress, size))
+            .map_err(|e| format!("Failed to add {} to archive: {}", name, e))?;
+        io::copy(&mut file, &mut self.writer)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to add {} to archive: {}", name, e))
+    }
+
+    fn add_bytes(&mut self, name: &str, bytes: &[u8], compress: bool) -> Result<(), String> {
+        self.writer.start_file(name, Self::options(compress, bytes.len() as u64))
+            .map_err(|e| format!("Failed to add {} to archive: {}", name, e))?;
+        self.writer.write_all(bytes)
+            .map_err(|e| format!("Failed to add {} to archive: {}", name, e))
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), String> {
+        let mut buffered = self.writer.finish()
+            .map_err(|e| format!("Failed to finish archive: {}", e))?;
+        buffered.flush()
+            .map_err(|e| format!("Failed to finish archive: {}", e))?;
+        drop(buffered);
+        fs::rename(&self.tmp_path, &self.destination)
+            .map_err(|e| format!("Failed to save archive: {}", e))
+    }
+}
+
+fn open_sink(destination: &Path, format: ExportFormat) -> Result<Box<dyn ExportSink>, String> {
+    match format {
+        ExportFormat::Folder => {
+            fs::create_dir_all(destination)
+                .map_err(|e| format!("Failed to create export folder: {}", e))?;
+            Ok(Box::new(FolderSink { root: destination.to_path_buf() }))
+        }
+        ExportFormat::Zip => {
+            let tmp_path = destination.with_extension("zip.tmp");
+            let file = fs::File::create(&tmp_path)
+                .map_err(|e| format!("Failed to create archive: {}", e))?;
+            Ok(Box::new(ZipSink {
+                writer: zip::ZipWriter::new(io::BufWriter::new(file)),
+                tmp_path,
+                destination: destination.to_path_buf(),
+            }))
+        }
+    }
+}
+
+/// Name of an image inside the export: its path under generated_images,
+/// so images with the same filename in different folders don't collide
+fn export_name(image: &ImageFile) -> String {
+    if image.folder.is_empty() {
+        image.filename.clone()
+    } else {
+        format!("{}/{}", image.folder, image.filename)
+    }
+}
+
+/// Take a free name in the export: `name` itself, or `stem-1.ext`, `stem-2.ext`...
+/// With `sidecar`, the matching `.json` name must be free too, so that images like
+/// `a.png` and `a.jpg` don't both write their metadata to `a.json`.
+fn claim_name(taken: &mut HashSet<String>, name: &str, sidecar: bool) -> String {
+    let json_name = |name: &str| Path::new(name).with_extension("json").to_string_lossy().replace('\\', "/");
+    let clashes = |taken: &HashSet<String>, name: &str| {
+        taken.contains(name) || (sidecar && taken.contains(&json_name(name)))
+    };
+
+    let path = Path::new(name);
+    let stem = path.with_extension("").to_string_lossy().replace('\\', "/");
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let claimed = std::iter::once(name.to_string())
+        .chain((1..).map(|n| format!("{}-{}{}", stem, n, extension)))
+        .find(|candidate| !clashes(taken, candidate))
+        .unwrap_or_else(|| name.to_string());
+
+    if sidecar {
+        taken.insert(json_name(&claimed));
+    }
+    taken.insert(claimed.clone());
+    claimed
+}
+
+/// Write each image, its JSON and the manifests to the sink.
+/// Returns the written image names and the number of bytes written.
+fn write_export(
+    app: &AppHandle,
+    sink: &mut dyn ExportSink,
+    images: &[ImageFile],
+    destination: &str,
+    options: &ExportOptions,
+    conversion: Option<&ConversionSettings>,
+) -> Result<(Vec<String>, u64), String> {
+    let mut manifest = Vec::new();
+    let mut files = Vec::new();
+    let mut taken = HashSet::new();
+    let mut total_bytes = 0;
+
+    // Stripping metadata from converted images means leaving the JSON out too
+    let include_metadata = options.include_metadata && conversion.is_none_or(|c| c.preserve_metadata);
+
+    for (i, image) in images.iter().enumerate() {
+        let source = Path::new(&image.path);
+        let json_path = source.with_extension("json");
+        let with_json = include_metadata && json_path.exists();
+
+        let name = match conversion {
+            Some(settings) => convert::output_name(&export_name(image), settings),
+            None => export_name(image),
+        };
+        let name = claim_name(&mut taken, &name, with_json);
+        match conversion {
+            Some(settings) => {
+                let bytes = convert::convert(image, settings)?;
+                sink.add_bytes(&name, &bytes, false)?;
+                total_bytes += bytes.len() as u64;
+            }
+            None => {
+                sink.add_file(&name, source, false)?;
+                total_bytes += fs::metadata(source).map(|m| m.len()).unwrap_or_default();
+            }
+        }
+
+        if with_json {
+            let json_name = Path::new(&name).with_extension("json").to_string_lossy().replace('\\', "/");
+            sink.add_file(&json_name, &json_path, true)?;
+            total_bytes += fs::metadata(&json_path).map(|m| m.len()).unwrap_or_default();
+        }
+
+        manifest.push(ManifestEntry::new(&name, image));
+        files.push(name.clone());
+
+        let _ = app.emit("export-progress", ExportProgress {
+            destination: destination.to_string(),
+            completed: i + 1,
+            total: images.len(),
+            current: name,
+        });
+    }
+
+    if options.manifest {
+        let manifest_json = serde_json::to_vec_pretty(&manifest)
+            .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+        sink.add_bytes("manifest.json", &manifest_json, true)?;
+        sink.add_bytes("manifest.csv", manifest_csv(&manifest).as_bytes(), true)?;
+    }
+
+    Ok((files, total_bytes))
+}
+
+/// Paths with repeats removed, keeping the first occurrence of each
+fn dedupe(paths: &[String]) -> Vec<&String> {
+    let mut seen = HashSet::new();
+    paths.iter().filter(|path| seen.insert(path.as_str())).collect()
+}
+
+/// Export images to a ZIP or folder, emitting `export-progress` after each image
+pub fn export(
+    app: &AppHandle,
+    catalog: &CatalogState,
+    project_path: &str,
+    paths: &[String],
+    destination: &Path,
+    options: &ExportOptions,
+) -> Result<ExportSummary, String> {
+    // The same image picked twice would clash with itself in the zip and the manifest
+    let images = dedupe(paths)
+        .into_iter()
+        .map(|path| {
+            catalog
+                .get(project_path, Path::new(path))
+                .ok_or_else(|| format!("Image not found in catalog: {}", path))
+        })
+        .collect::<Result<Vec<ImageFile>, String>>()?;
+
+    let conversion = convert::resolve_settings(project_path, options.conversion.clone(), options.preset.as_deref())?;
+
+    let destination_string = destination.to_string_lossy().to_string();
+    let mut sink = open_sink(destination, options.format)?;
+    let result = write_export(app, sink.as_mut(), &images, &destination_string, options, conversion.as_ref())
+        .and_then(|written| sink.finish().map(|_| written));
+
+    let (files, total_bytes) = match result {
+        Ok(written) => written,
+        Err(e) => {
+            // Don't leave a half-written archive behind
+            if let ExportFormat::Zip = options.format {
+                let _ = fs::remove_file(destination.with_extension("zip.tmp"));
+            }
+            return Err(e);
+        }
+    };
+
+    Ok(ExportSummary {
+        destination: destination_string,
+        image_count: images.len(),
+        total_bytes,
+        files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_csv_quotes_fields() {
+        let entry = ManifestEntry {
+            file: "client/generated_1.png".to_string(),
+            prompt: Some("A \"golden\" door, at dusk".to_string()),
+            model: Some("gemini-2.5-flash-image".to_string()),
+            aspect_ratio: None,
+            image_size: None,
+            width: Some(1024),
+            height: Some(1024),
+            timestamp: None,
+            reference_images: vec!["a.png".to_string(), "b.png".to_string()],
+        };
+
+        let csv = manifest_csv(&[entry]);
+        let row = csv.lines().nth(1).unwrap();
+        assert_eq!(
+            row,
+            "client/generated_1.png,\"A \"\"golden\"\" door, at dusk\",gemini-2.5-flash-image,,,1024,1024,,a.png;b.png"
+        );
+    }
+
+    #[test]
+    fn test_repeated_paths_and_clashing_names() {
+        let paths = ["/b.png", "/a.png", "/b.png", "/c.png", "/a.png"].map(String::from);
+        assert_eq!(dedupe(&paths), ["/b.png", "/a.png", "/c.png"]);
+
+        // a.png and a.jpg would both write their metadata to a.json
+        let mut taken = HashSet::new();
+        assert_eq!(claim_name(&mut taken, "client/a.png", true), "client/a.png");
+        assert_eq!(claim_name(&mut taken, "client/a.jpg", true), "client/a-1.jpg");
+        assert!(taken.contains("client/a-1.json"));
+        assert_eq!(claim_name(&mut taken, "client/a.jpg", false), "client/a.jpg");
+
+        // Converting both to WebP gives them the same name
+        let settings: ConversionSettings = serde_json::from_str(r#"{"format": "webp"}"#).unwrap();
+        let mut taken = HashSet::new();
+        let names: Vec<String> = ["a.png", "a.jpg", "a.jpeg"]
+            .iter()
+            .map(|name| claim_name(&mut taken, &convert::output_name(name, &settings), false))
+            .collect();
+        assert_eq!(names, ["a.webp", "a-1.webp", "a-2.webp"]);
+    }
+}
@@ -0,0 +1,129 @@
+//! Supported image formats for the gallery
+//! Extensions decide which files are candidates; the file header decides what they are
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Webp,
+    Gif,
+    Avif,
+    Heic,
+}
+
+/// Every extension we treat as a possible image, lowercase
+pub const IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "webp", "gif", "avif", "heic", "heif",
+];
+
+impl ImageFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Webp => "image/webp",
+            ImageFormat::Gif => "image/gif",
+            ImageFormat::Avif => "image/avif",
+            ImageFormat::Heic => "image/heic",
+        }
+    }
+
+    /// Whether we can decode pixels (for thumbnails and the like), not just list the file.
+    /// AVIF and HEIC need native codecs we don't bundle.
+    pub fn is_decodable(self) -> bool {
+        matches!(self, ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Webp | ImageFormat::Gif)
+    }
+}
+
+/// Whether a path has one of the supported image extensions
+pub fn is_image_path(path: &Path) -> bool {
+    match path.extension() {
+        Some(ext) => {
+            let ext_lower = ext.to_string_lossy().to_lowercase();
+            IMAGE_EXTENSIONS.contains(&ext_lower.as_str())
+        }
+        None => false,
+    }
+}
+
+/// Brands in an ISO base media file's `ftyp` box (major brand first)
+fn ftyp_brands(header: &[u8]) -> Vec<&[u8]> {
+    if header.len() < 16 || &header[4..8] != b"ftyp" {
+        return Vec::new();
+    }
+
+    let box_size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let end = box_size.min(header.len());
+
+    // Major brand, then (after the minor version) the compatible brands
+    let mut brands = vec![&header[8..12]];
+    let mut offset = 16;
+    while offset + 4 <= end {
+        brands.push(&header[offset..offset + 4]);
+        offset += 4;
+    }
+    brands
+}
+
+/// Identify an image format from the first bytes of a file
+pub fn sniff(header: &[u8]) -> Option<ImageFormat> {
+    if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some(ImageFormat::Png);
+    }
+    if header.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return Some(ImageFormat::Jpeg);
+    }
+    if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+        return Some(ImageFormat::Gif);
+    }
+    if header.len() >= 12 && &header[0..4] == b"RIFF" && &header[8..12] == b"WEBP" {
+        return Some(ImageFormat::Webp);
+    }
+
+    let brands = ftyp_brands(header);
+    if brands.iter().any(|b| *b == b"avif" || *b == b"avis") {
+        return Some(ImageFormat::Avif);
+    }
+    let heif_brands: [&[u8]; 8] = [b"heic", b"heix", b"hevc", b"hevx", b"heim", b"heis", b"mif1", b"msf1"];
+    if brands.iter().any(|b| heif_brands.contains(b)) {
+        return Some(ImageFormat::Heic);
+    }
+
+    None
+}
+
+/// Read a file's header and identify its format
+pub fn detect(path: &Path) -> Option<ImageFormat> {
+    let mut header = [0u8; 64];
+    let mut file = File::open(path).ok()?;
+    let mut read = 0;
+
+    // A single read may return less than asked for
+    while read < header.len() {
+        match file.read(&mut header[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(_) => return None,
+        }
+    }
+
+    sniff(&header[..read])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_recognises_headers() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0"), Some(ImageFormat::Png));
+        assert_eq!(sniff(b"RIFF\x10\0\0\0WEBPVP8 "), Some(ImageFormat::Webp));
+        assert_eq!(sniff(b"\0\0\0\x1cftypavif\0\0\0\0avifmif1miaf"), Some(ImageFormat::Avif));
+        assert_eq!(sniff(b"\0\0\0\x18ftypheic\0\0\0\0mif1heic"), Some(ImageFormat::Heic));
+        assert_eq!(sniff(b"<html>"), None);
+    }
+}
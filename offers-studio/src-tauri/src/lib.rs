@@ -1,36 +1,34 @@
 mod pty;
+mod pty_output;
+mod scrollback;
 mod watcher;
 mod context_watcher;
 mod sessions;
 mod setup;
+mod annotations;
+mod catalog;
+mod collections;
+mod compare;
+mod convert;
+mod embed;
+mod export;
+mod formats;
+mod lineage;
+mod metadata;
+mod properties;
+mod query;
+mod search;
+mod settle;
+mod similarity;
+mod thumbnails;
+mod trash;
 
 use std::fs;
-use std::path::{Path, PathBuf};
-use std::sync::Mutex;
-use serde::Serialize;
+use std::path::PathBuf;
 use tauri::{AppHandle, Manager, State};
 
-#[derive(Debug, Serialize)]
-struct ImageMetadata {
-    prompt: Option<String>,
-    model: Option<String>,
-    timestamp: Option<String>,
-    aspect_ratio: Option<String>,
-    size: Option<String>,
-    generation_time: Option<f64>,
-    reference_images: Option<Vec<String>>,
-}
-
-#[derive(Debug, Serialize)]
-struct ImageFile {
-    path: String,
-    filename: String,
-    timestamp: String,
-    metadata: Option<ImageMetadata>,
-}
-
-// Global PTY manager state
-struct PtyState(Mutex<pty::PtyManager>);
+// Global PTY manager state; the manager locks internally, per PTY where it can
+struct PtyState(pty::PtyManager);
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
@@ -56,40 +54,90 @@ fn spawn_pty(
     cols: u16,
     rows: u16,
 ) -> Result<String, String> {
-    let manager = state.0.lock().unwrap();
-    manager.spawn(app, command, args, cwd, cols, rows)
+    state.0.spawn(app, command, args, cwd, cols, rows)
 }
 
-#[tauri::command]
+/// Send input to a PTY. Runs off the main thread since it blocks while the child
+/// isn't reading, e.g. during a large paste while output is held back.
+#[tauri::command(async)]
 fn write_pty(state: State<PtyState>, id: String, data: String) -> Result<(), String> {
-    let manager = state.0.lock().unwrap();
-    manager.write(&id, &data)
+    state.0.write(&id, &data)
 }
 
 #[tauri::command]
 fn resize_pty(state: State<PtyState>, id: String, cols: u16, rows: u16) -> Result<(), String> {
-    let manager = state.0.lock().unwrap();
-    manager.resize(&id, cols, rows)
+    state.0.resize(&id, cols, rows)
+}
+
+/// Acknowledge a batch of PTY output once the terminal has written it
+#[tauri::command]
+fn ack_pty(state: State<PtyState>, id: String) -> Result<(), String> {
+    state.0.ack(&id)
+}
+
+/// Output metrics for a PTY: bytes read, events emitted, backpressure and latency
+#[tauri::command]
+fn get_pty_metrics(
+    state: State<PtyState>,
+    id: String,
+) -> Result<pty_output::OutputMetricsSnapshot, String> {
+    state.0.metrics(&id)
+}
+
+/// Terminals that were running when the app last quit, with their scrollback
+#[tauri::command]
+fn get_saved_terminals(app: AppHandle) -> Result<Vec<pty::SavedTerminal>, String> {
+    pty::load_saved_terminals(&app)
 }
 
+/// Every PTY with its command, pid and whether it's still running
 #[tauri::command]
-fn kill_pty(state: State<PtyState>, id: String) -> Result<(), String> {
-    let manager = state.0.lock().unwrap();
-    manager.kill(&id)
+fn list_ptys(state: State<PtyState>) -> Vec<pty::PtyInfo> {
+    state.0.list()
+}
+
+/// Fetch a PTY's recent output to replay after a reload
+#[tauri::command]
+fn get_pty_scrollback(
+    state: State<PtyState>,
+    id: String,
+    since: Option<u64>,
+) -> Result<scrollback::ScrollbackSnapshot, String> {
+    state.0.scrollback(&id, since)
+}
+
+/// Terminate a PTY and its subprocesses, escalating signals until they're gone
+#[tauri::command(async)]
+fn kill_pty(state: State<PtyState>, id: String) -> Result<pty::KillStage, String> {
+    state.0.kill(&id)
 }
 
 #[tauri::command]
 fn start_watcher(
     app: AppHandle,
     state: State<watcher::WatcherState>,
+    catalog: State<catalog::CatalogState>,
     path: String,
 ) -> Result<(), String> {
+    // Pick up anything that changed while we weren't watching
+    catalog.reconcile(&path)?;
+    // Thumbnails of images that were deleted or rewritten meanwhile are no longer needed
+    if let Err(e) = thumbnails::prune(&app, &path, &catalog.content_hashes(&path)) {
+        eprintln!("{}", e);
+    }
     state.start(&app, &path).map_err(|e| e.to_string())
 }
 
+/// Stop watching a project (other windows showing it keep their watcher)
 #[tauri::command]
-fn stop_watcher(state: State<watcher::WatcherState>) {
-    state.stop();
+fn stop_watcher(state: State<watcher::WatcherState>, path: String) {
+    state.stop(&path);
+}
+
+/// Projects whose generated_images are currently being watched
+#[tauri::command]
+fn list_watchers(state: State<watcher::WatcherState>) -> Vec<String> {
+    state.watched_projects()
 }
 
 /// Get the path where the statusline script should be installed
@@ -287,85 +335,484 @@ fn set_session_name(session_id: String, name: String) -> Result<(), String> {
     sessions::set_session_name(&session_id, &name)
 }
 
+/// List all images in a project's generated_images folder from the catalog index
+#[tauri::command(async)]
+fn list_images(
+    state: State<catalog::CatalogState>,
+    project_path: String,
+) -> Result<Vec<catalog::ImageFile>, String> {
+    Ok(state.list(&project_path))
+}
+
+/// Rewrite a project's companion JSON files in the current metadata schema.
+/// Returns how many files were upgraded.
 #[tauri::command]
-fn list_images(project_path: String) -> Result<Vec<ImageFile>, String> {
-    let images_dir = Path::new(&project_path).join("generated_images");
+fn migrate_metadata(
+    state: State<catalog::CatalogState>,
+    project_path: String,
+) -> Result<usize, String> {
+    let mut upgraded = 0;
+
+    for image in state.list(&project_path) {
+        let json_path = std::path::Path::new(&image.path).with_extension("json");
+        if json_path.exists() && metadata::upgrade_file(&json_path)? {
+            upgraded += 1;
+        }
+    }
+
+    // The watcher may not be running, so make sure the catalog sees the new files
+    state.reconcile(&project_path)?;
+
+    Ok(upgraded)
+}
+
+/// Write an image's generation metadata into the file itself
+/// (an iTXt chunk for PNG, XMP for JPEG) so it survives being shared
+#[tauri::command]
+fn embed_metadata(
+    app: AppHandle,
+    state: State<catalog::CatalogState>,
+    project_path: String,
+    path: String,
+) -> Result<(), String> {
+    let image_path = std::path::Path::new(&path);
+    let image = state
+        .get(&project_path, image_path)
+        .ok_or_else(|| format!("Image not found in catalog: {}", path))?;
+    let metadata = image
+        .metadata
+        .ok_or_else(|| format!("No metadata to embed for {}", path))?;
+    let format = formats::detect(image_path)
+        .ok_or_else(|| format!("Not a supported image: {}", path))?;
+
+    // Rewriting the file changes its content hash, so annotations need moving over
+    let old_hash = state.content_hash(&project_path, image_path)?;
+    embed::write(image_path, format, &metadata)?;
+
+    // Refresh the entry and report the change without waiting for the watcher
+    app.state::<watcher::WatcherState>().expect_own_change(image_path);
+    watcher::emit_settled(&app, &project_path, vec![settle::ImageEvent::Modified(image_path.to_path_buf())]);
+    state.rekey_annotation(&project_path, image_path, &old_hash)
+}
+
+/// Find images that look like the given one, closest first.
+/// The first call decodes every image in the project to hash it, so it runs off the main thread.
+#[tauri::command(async)]
+fn find_similar(
+    state: State<catalog::CatalogState>,
+    project_path: String,
+    path: String,
+    threshold: Option<u32>,
+) -> Result<Vec<similarity::SimilarImage>, String> {
+    let fingerprints = state.fingerprints(&project_path, true)?;
+    similarity::find_similar(
+        &fingerprints,
+        std::path::Path::new(&path),
+        threshold.unwrap_or(similarity::DEFAULT_THRESHOLD),
+    )
+}
 
-    if !images_dir.exists() {
-        // Return empty list if directory doesn't exist yet
-        return Ok(Vec::new());
+/// Group a project's images into duplicates for cleanup.
+/// Exact duplicates only need content hashes; near-duplicates need every image decoded.
+#[tauri::command(async)]
+fn find_duplicates(
+    state: State<catalog::CatalogState>,
+    project_path: String,
+    near: Option<bool>,
+    threshold: Option<u32>,
+) -> Result<Vec<similarity::DuplicateGroup>, String> {
+    let near = near.unwrap_or(false);
+    let fingerprints = state.fingerprints(&project_path, near)?;
+
+    if near {
+        Ok(similarity::near_duplicates(
+            &fingerprints,
+            threshold.unwrap_or(similarity::DEFAULT_THRESHOLD),
+        ))
+    } else {
+        Ok(similarity::exact_duplicates(&fingerprints))
     }
+}
+
+/// Parents, children and ancestors of an image, from the references recorded in its metadata
+#[tauri::command(async)]
+fn get_lineage(
+    state: State<catalog::CatalogState>,
+    project_path: String,
+    path: String,
+) -> Result<lineage::Lineage, String> {
+    let graph = lineage::LineageGraph::build(&project_path, state.list(&project_path));
+    graph.lineage(&path)
+}
 
-    let mut images = Vec::new();
+/// Every recorded reference in a project that no longer exists on disk
+#[tauri::command]
+fn find_missing_references(
+    state: State<catalog::CatalogState>,
+    project_path: String,
+) -> Vec<lineage::MissingReference> {
+    let graph = lineage::LineageGraph::build(&project_path, state.list(&project_path));
+    graph.missing_references()
+}
 
-    let entries = fs::read_dir(&images_dir)
-        .map_err(|e| format!("Failed to read directory: {}", e))?;
+/// Export images with their metadata and a manifest to a ZIP or folder.
+/// Progress is reported through `export-progress` events.
+#[tauri::command(async)]
+fn export_images(
+    app: AppHandle,
+    state: State<catalog::CatalogState>,
+    project_path: String,
+    paths: Vec<String>,
+    destination: String,
+    options: Option<export::ExportOptions>,
+) -> Result<export::ExportSummary, String> {
+    export::export(
+        &app,
+        &state,
+        &project_path,
+        &paths,
+        std::path::Path::new(&destination),
+        &options.unwrap_or_default(),
+    )
+}
 
-    for entry in entries {
-        let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
-        let path = entry.path();
+/// Convert images (resize, change format, set JPEG quality) into a folder.
+/// Uses the given settings, or a saved preset by name. Returns the written paths.
+/// WebP output is lossless only, so settings with a quality for it are rejected;
+/// use JPEG when a smaller, lossy file is wanted.
+#[tauri::command(async)]
+fn convert_images(
+    app: AppHandle,
+    state: State<catalog::CatalogState>,
+    project_path: String,
+    paths: Vec<String>,
+    destination: String,
+    settings: Option<convert::ConversionSettings>,
+    preset: Option<String>,
+) -> Result<Vec<String>, String> {
+    let settings = convert::resolve_settings(&project_path, settings, preset.as_deref())?
+        .ok_or("No conversion settings or preset given")?;
+    let destination_dir = std::path::Path::new(&destination);
+
+    let summary = export::export(
+        &app,
+        &state,
+        &project_path,
+        &paths,
+        destination_dir,
+        &export::ExportOptions {
+            format: export::ExportFormat::Folder,
+            include_metadata: false,
+            manifest: false,
+            conversion: Some(settings),
+            preset: None,
+        },
+    )?;
+
+    Ok(summary
+        .files
+        .iter()
+        .map(|name| destination_dir.join(name).to_string_lossy().to_string())
+        .collect())
+}
 
-        // Only process PNG and JPG files
-        if let Some(ext) = path.extension() {
-            let ext_lower = ext.to_string_lossy().to_lowercase();
-            if !matches!(ext_lower.as_str(), "png" | "jpg" | "jpeg") {
-                continue;
+/// List a project's saved export presets
+#[tauri::command]
+fn list_export_presets(project_path: String) -> Vec<convert::ExportPreset> {
+    convert::load_presets(&project_path)
+}
+
+/// Save an export preset, replacing any with the same name.
+/// Quality applies to JPEG only; PNG and WebP presets must leave it unset.
+#[tauri::command]
+fn save_export_preset(project_path: String, preset: convert::ExportPreset) -> Result<(), String> {
+    convert::save_preset(&project_path, preset)
+}
+
+/// Delete an export preset. Returns false if it didn't exist.
+#[tauri::command]
+fn delete_export_preset(project_path: String, name: String) -> Result<bool, String> {
+    convert::delete_preset(&project_path, &name)
+}
+
+/// Move images and their companion JSON to the project's trash
+#[tauri::command]
+fn delete_images(
+    app: AppHandle,
+    project_path: String,
+    paths: Vec<String>,
+) -> Result<Vec<trash::TrashItem>, String> {
+    let mut trashed = Vec::new();
+    let mut events = Vec::new();
+    let mut result = Ok(());
+
+    for path in &paths {
+        match trash::delete(&project_path, std::path::Path::new(path)) {
+            Ok(item) => {
+                trashed.push(item);
+                events.push(settle::ImageEvent::Removed(PathBuf::from(path)));
+            }
+            Err(e) => {
+                result = Err(e);
+                break;
             }
-        } else {
-            continue;
         }
+    }
 
-        let filename = path.file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_default();
-
-        // Get file modification time as timestamp
-        let metadata = fs::metadata(&path)
-            .map_err(|e| format!("Failed to read metadata: {}", e))?;
-
-        let timestamp = metadata.modified()
-            .map(|t| {
-                let duration = t.duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
-                chrono::DateTime::from_timestamp(duration.as_secs() as i64, 0)
-                    .map(|dt| dt.to_rfc3339())
-                    .unwrap_or_default()
-            })
-            .unwrap_or_default();
-
-        // Try to load metadata from companion JSON file
-        let json_path = path.with_extension("json");
-        let image_metadata = if json_path.exists() {
-            fs::read_to_string(&json_path)
-                .ok()
-                .and_then(|content| {
-                    serde_json::from_str::<serde_json::Value>(&content).ok()
-                })
-                .map(|json| ImageMetadata {
-                    prompt: json.get("prompt").and_then(|v| v.as_str()).map(String::from),
-                    model: json.get("model").and_then(|v| v.as_str()).map(String::from),
-                    timestamp: json.get("timestamp").and_then(|v| v.as_str()).map(String::from),
-                    aspect_ratio: json.get("aspect_ratio").and_then(|v| v.as_str()).map(String::from),
-                    size: json.get("size").and_then(|v| v.as_str()).map(String::from),
-                    generation_time: json.get("generation_time").and_then(|v| v.as_f64()),
-                    reference_images: json.get("reference_images")
-                        .and_then(|v| v.as_array())
-                        .map(|arr| arr.iter()
-                            .filter_map(|v| v.as_str().map(String::from))
-                            .collect()),
-                })
-        } else {
-            None
-        };
-
-        images.push(ImageFile {
-            path: path.to_string_lossy().to_string(),
-            filename,
-            timestamp,
-            metadata: image_metadata,
-        });
+    // Tell the gallery about whatever did get trashed, even if a later image failed
+    watcher::emit_settled(&app, &project_path, events);
+    result.map(|_| trashed)
+}
+
+/// List a project's trash, most recently deleted first
+#[tauri::command]
+fn list_trash(project_path: String) -> Vec<trash::TrashItem> {
+    trash::list(&project_path)
+}
+
+/// Restore trashed images, returning the paths they were restored to
+#[tauri::command]
+fn restore_from_trash(
+    app: AppHandle,
+    project_path: String,
+    ids: Vec<String>,
+) -> Result<Vec<String>, String> {
+    let mut restored = Vec::new();
+    let mut result = Ok(());
+
+    let watchers = app.state::<watcher::WatcherState>();
+    for id in &ids {
+        match trash::restore(&project_path, id) {
+            Ok(path) => {
+                watchers.expect_own_change(&path);
+                restored.push(path);
+            }
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
     }
 
-    Ok(images)
+    // Reported here so the gallery updates straight away, so the watcher needn't
+    let events = restored.iter().cloned().map(settle::ImageEvent::Added).collect();
+    watcher::emit_settled(&app, &project_path, events);
+    result.map(|_| {
+        restored
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect()
+    })
+}
+
+/// Permanently delete trashed images (all of them if no ids are given).
+/// Returns the number of bytes freed.
+#[tauri::command]
+fn empty_trash(project_path: String, ids: Option<Vec<String>>) -> Result<u64, String> {
+    trash::empty(&project_path, ids.as_deref())
+}
+
+/// Tag, rate, favourite or add notes to one or more images.
+/// Returns the updated images.
+#[tauri::command(async)]
+fn annotate_images(
+    state: State<catalog::CatalogState>,
+    project_path: String,
+    paths: Vec<String>,
+    update: annotations::AnnotationUpdate,
+) -> Result<Vec<catalog::ImageFile>, String> {
+    let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+    state.annotate(&project_path, &paths, &update)
+}
+
+/// Tags used in a project with how many images carry each, most used first
+#[tauri::command]
+fn list_tags(
+    state: State<catalog::CatalogState>,
+    project_path: String,
+) -> Vec<annotations::TagCount> {
+    state.tags(&project_path)
+}
+
+/// List every collection, in display order
+#[tauri::command]
+fn list_collections(
+    app: AppHandle,
+    state: State<collections::CollectionState>,
+) -> Result<Vec<collections::Collection>, String> {
+    state.list(&app)
+}
+
+/// Get a collection with its images, following any that have moved.
+/// Finding a moved image can mean hashing whole projects, so it runs off the main thread.
+#[tauri::command(async)]
+fn get_collection(
+    app: AppHandle,
+    state: State<collections::CollectionState>,
+    catalog: State<catalog::CatalogState>,
+    id: String,
+) -> Result<collections::ResolvedCollection, String> {
+    state.resolve(&app, &catalog, &id)
+}
+
+#[tauri::command]
+fn create_collection(
+    app: AppHandle,
+    state: State<collections::CollectionState>,
+    name: String,
+) -> Result<collections::Collection, String> {
+    state.create(&app, &name)
+}
+
+#[tauri::command]
+fn rename_collection(
+    app: AppHandle,
+    state: State<collections::CollectionState>,
+    id: String,
+    name: String,
+) -> Result<(), String> {
+    state.rename(&app, &id, &name)
+}
+
+#[tauri::command]
+fn delete_collection(
+    app: AppHandle,
+    state: State<collections::CollectionState>,
+    id: String,
+) -> Result<(), String> {
+    state.delete(&app, &id)
+}
+
+/// Reorder collections to match the given ids
+#[tauri::command]
+fn reorder_collections(
+    app: AppHandle,
+    state: State<collections::CollectionState>,
+    ids: Vec<String>,
+) -> Result<(), String> {
+    state.reorder(&app, &ids)
+}
+
+/// Add images from a project to a collection
+#[tauri::command(async)]
+fn add_to_collection(
+    app: AppHandle,
+    state: State<collections::CollectionState>,
+    catalog: State<catalog::CatalogState>,
+    id: String,
+    project_path: String,
+    paths: Vec<String>,
+) -> Result<(), String> {
+    state.add(&app, &catalog, &id, &project_path, &paths)
+}
+
+#[tauri::command]
+fn remove_from_collection(
+    app: AppHandle,
+    state: State<collections::CollectionState>,
+    id: String,
+    paths: Vec<String>,
+) -> Result<(), String> {
+    state.remove(&app, &id, &paths)
+}
+
+/// Reorder a collection's images to match the given paths
+#[tauri::command]
+fn reorder_collection_items(
+    app: AppHandle,
+    state: State<collections::CollectionState>,
+    id: String,
+    paths: Vec<String>,
+) -> Result<(), String> {
+    state.reorder_items(&app, &id, &paths)
+}
+
+/// Compare two or more images against the first: metadata, prompt diff and pixel heat maps
+#[tauri::command(async)]
+fn compare_images(
+    app: AppHandle,
+    state: State<catalog::CatalogState>,
+    project_path: String,
+    paths: Vec<String>,
+) -> Result<compare::Comparison, String> {
+    compare::compare(&app, &state, &project_path, &paths)
+}
+
+/// Full-text search over image prompts and descriptions
+#[tauri::command(async)]
+fn search_images(
+    state: State<catalog::CatalogState>,
+    project_path: String,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<catalog::SearchHit>, String> {
+    Ok(state.search(&project_path, &query, limit.unwrap_or(50)))
+}
+
+/// Folder tree of generated_images with image counts
+#[tauri::command]
+fn list_image_folders(
+    state: State<catalog::CatalogState>,
+    project_path: String,
+) -> Result<catalog::FolderNode, String> {
+    Ok(state.folders(&project_path))
+}
+
+/// Get a cached, downscaled thumbnail for an image, generating it if needed.
+/// Returns the thumbnail's file path for use with convertFileSrc.
+/// Runs off the main thread since decoding a 4K image takes a while.
+#[tauri::command(async)]
+fn get_thumbnail(
+    app: AppHandle,
+    state: State<catalog::CatalogState>,
+    project_path: String,
+    path: String,
+    size: Option<thumbnails::ThumbnailSize>,
+) -> Result<String, String> {
+    let thumbnail = thumbnails::get_or_create(
+        &app,
+        &state,
+        &project_path,
+        std::path::Path::new(&path),
+        size.unwrap_or_default(),
+    )?;
+    Ok(thumbnail.to_string_lossy().to_string())
+}
+
+/// Delete all cached thumbnails, returning the number of bytes freed
+#[tauri::command]
+fn clear_thumbnail_cache(app: AppHandle) -> Result<u64, String> {
+    thumbnails::clear_cache(&app)
+}
+
+/// Query a page of images with sorting and filters applied
+#[tauri::command(async)]
+fn query_images(
+    state: State<catalog::CatalogState>,
+    project_path: String,
+    query: query::ImageQuery,
+) -> Result<query::ImagePage, String> {
+    Ok(query.run(state.list(&project_path)))
+}
+
+/// Stop watchers, flush catalogs and terminate every PTY, saving terminals for next launch
+fn shutdown(app: &AppHandle) {
+    if let Some(state) = app.try_state::<watcher::WatcherState>() {
+        state.stop_all();
+    }
+    if let Some(state) = app.try_state::<context_watcher::ContextWatcherState>() {
+        state.stop();
+    }
+    if let Some(state) = app.try_state::<catalog::CatalogState>() {
+        state.flush_all();
+    }
+    if let Some(state) = app.try_state::<PtyState>() {
+        // PTYs are terminated in parallel, so this takes as long as the slowest one
+        let terminals = state.0.shutdown();
+        if let Err(e) = pty::save_terminals(app, &terminals) {
+            eprintln!("Failed to save terminals: {}", e);
+        }
+    }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -375,8 +822,10 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
-        .manage(PtyState(Mutex::new(pty::PtyManager::new())))
+        .manage(PtyState(pty::PtyManager::new()))
         .manage(watcher::WatcherState::new())
+        .manage(catalog::CatalogState::new())
+        .manage(collections::CollectionState::new())
         .manage(context_watcher::ContextWatcherState::new())
         .manage(setup::ProjectPathState::new())
         .invoke_handler(tauri::generate_handler![
@@ -386,9 +835,47 @@ pub fn run() {
             write_pty,
             resize_pty,
             kill_pty,
+            get_pty_scrollback,
+            list_ptys,
+            get_pty_metrics,
+            ack_pty,
+            get_saved_terminals,
             start_watcher,
             stop_watcher,
+            list_watchers,
             list_images,
+            query_images,
+            search_images,
+            annotate_images,
+            list_tags,
+            list_collections,
+            get_collection,
+            create_collection,
+            rename_collection,
+            delete_collection,
+            reorder_collections,
+            add_to_collection,
+            remove_from_collection,
+            reorder_collection_items,
+            compare_images,
+            list_image_folders,
+            get_thumbnail,
+            clear_thumbnail_cache,
+            migrate_metadata,
+            embed_metadata,
+            find_similar,
+            find_duplicates,
+            get_lineage,
+            find_missing_references,
+            delete_images,
+            list_trash,
+            restore_from_trash,
+            empty_trash,
+            export_images,
+            convert_images,
+            list_export_presets,
+            save_export_preset,
+            delete_export_preset,
             install_statusline,
             configure_claude_statusline,
             check_statusline,
@@ -411,16 +898,13 @@ pub fn run() {
             setup::get_uv_version,
             setup::install_uv
         ])
-        .on_window_event(|window, event| {
-            if let tauri::WindowEvent::CloseRequested { .. } = event {
-                if let Some(state) = window.try_state::<watcher::WatcherState>() {
-                    state.stop();
-                }
-                if let Some(state) = window.try_state::<context_watcher::ContextWatcherState>() {
-                    state.stop();
-                }
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        // Clean up once the app is really exiting, rather than when a close is requested
+        // (which can be vetoed) and without blocking the UI while terminals shut down
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                shutdown(app);
             }
-        })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        });
 }
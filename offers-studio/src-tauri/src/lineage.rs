@@ -0,0 +1,248 @@
+//! Image lineage built from the reference_images each generation recorded
+//! Lets us walk from a final asset back through the iterations and references
+//! that produced it, and forward to everything generated from an image
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Component, Path, PathBuf};
+use serde::Serialize;
+use crate::catalog::{get_images_dir, ImageFile};
+
+/// A referenced image, which may live outside generated_images or be gone entirely
+#[derive(Debug, Clone, Serialize)]
+pub struct LineageNode {
+    pub path: String,
+    /// Set when the image is in the gallery
+    pub image: Option<ImageFile>,
+    pub exists: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Ancestor {
+    #[serde(flatten)]
+    pub node: LineageNode,
+    /// 1 for direct references, 2 for their references, and so on
+    pub depth: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Lineage {
+    pub image: ImageFile,
+    pub parents: Vec<LineageNode>,
+    /// Gallery images that used this one as a reference
+    pub children: Vec<ImageFile>,
+    /// Every image this one descends from, nearest first
+    pub ancestors: Vec<Ancestor>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MissingReference {
+    pub image: ImageFile,
+    /// The reference as recorded in the metadata
+    pub reference: String,
+    /// Where we looked for it
+    pub resolved: String,
+}
+
+/// Clean up `.` and `..` without touching the disk, so paths compare equal to catalog paths
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Parent/child links between every image in a project
+pub struct LineageGraph {
+    images: HashMap<String, ImageFile>,
+    /// Child path to its resolved references, in recorded order
+    parents: HashMap<String, Vec<String>>,
+    children: HashMap<String, Vec<String>>,
+    /// (child, recorded reference, resolved path) for references that don't exist
+    missing: Vec<(String, String, String)>,
+}
+
+impl LineageGraph {
+    pub fn build(project_path: &str, images: Vec<ImageFile>) -> Self {
+        let images: HashMap<String, ImageFile> = images
+            .into_iter()
+            .map(|image| (image.path.clone(), image))
+            .collect();
+
+        let mut graph = Self {
+            images: HashMap::new(),
+            parents: HashMap::new(),
+            children: HashMap::new(),
+            missing: Vec::new(),
+        };
+
+        for (path, image) in &images {
+            let references = image.metadata.as_ref().map(|m| m.reference_images.as_slice()).unwrap_or_default();
+            for reference in references {
+                let (resolved, exists) = resolve(project_path, Path::new(path), reference, &images);
+                if !exists {
+                    graph.missing.push((path.clone(), reference.clone(), resolved.clone()));
+                }
+                graph.parents.entry(path.clone()).or_default().push(resolved.clone());
+                graph.children.entry(resolved).or_default().push(path.clone());
+            }
+        }
+
+        for children in graph.children.values_mut() {
+            children.sort();
+            children.dedup();
+        }
+        graph.images = images;
+        graph
+    }
+
+    fn node(&self, path: &str) -> LineageNode {
+        let image = self.images.get(path).cloned();
+        LineageNode {
+            path: path.to_string(),
+            exists: image.is_some() || Path::new(path).exists(),
+            image,
+        }
+    }
+
+    /// Parents, children and ancestors of a gallery image
+    pub fn lineage(&self, path: &str) -> Result<Lineage, String> {
+        let image = self.images
+            .get(path)
+            .cloned()
+            .ok_or_else(|| format!("Image not found in catalog: {}", path))?;
+
+        let parent_paths = self.parents.get(path).cloned().unwrap_or_default();
+        let parents = parent_paths.iter().map(|p| self.node(p)).collect();
+
+        let children = self.children
+            .get(path)
+            .map(|paths| paths.iter().filter_map(|p| self.images.get(p).cloned()).collect())
+            .unwrap_or_default();
+
+        // Breadth-first so nearer ancestors come first; the visited set guards
+        // against cycles from an image being overwritten by its own descendant
+        let mut ancestors = Vec::new();
+        let mut visited: HashSet<&str> = HashSet::from([path]);
+        let mut queue: VecDeque<(&str, usize)> = VecDeque::from([(path, 0)]);
+        while let Some((current, depth)) = queue.pop_front() {
+            for parent in self.parents.get(current).into_iter().flatten() {
+                if visited.insert(parent) {
+                    ancestors.push(Ancestor { node: self.node(parent), depth: depth + 1 });
+                    queue.push_back((parent, depth + 1));
+                }
+            }
+        }
+
+        Ok(Lineage { image, parents, children, ancestors })
+    }
+
+    /// References, across the whole project, to images that no longer exist
+    pub fn missing_references(&self) -> Vec<MissingReference> {
+        let mut missing: Vec<MissingReference> = self.missing
+            .iter()
+            .filter_map(|(child, reference, resolved)| {
+                Some(MissingReference {
+                    image: self.images.get(child)?.clone(),
+                    reference: reference.clone(),
+                    resolved: resolved.clone(),
+                })
+            })
+            .collect();
+        missing.sort_by(|a, b| a.image.path.cmp(&b.image.path).then_with(|| a.reference.cmp(&b.reference)));
+        missing
+    }
+}
+
+/// Work out which file a recorded reference points at.
+/// The generator records paths as passed on the command line, which is normally run
+/// from the project root, but relative to the image or generated_images are tried too.
+/// Returns the resolved path and whether it exists.
+fn resolve(
+    project_path: &str,
+    image_path: &Path,
+    reference: &str,
+    images: &HashMap<String, ImageFile>,
+) -> (String, bool) {
+    let reference_path = Path::new(reference);
+    let candidates: Vec<PathBuf> = if reference_path.is_absolute() {
+        vec![normalize(reference_path)]
+    } else {
+        let project = Path::new(project_path);
+        let mut candidates = vec![normalize(&project.join(reference_path))];
+        if let Some(folder) = image_path.parent() {
+            candidates.push(normalize(&folder.join(reference_path)));
+        }
+        candidates.push(normalize(&get_images_dir(project_path).join(reference_path)));
+        candidates
+    };
+
+    let found = candidates
+        .iter()
+        .map(|c| c.to_string_lossy().to_string())
+        .find(|c| images.contains_key(c) || Path::new(c).exists());
+
+    match found {
+        Some(path) => (path, true),
+        None => (candidates[0].to_string_lossy().to_string(), false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::ImageMetadata;
+
+    fn image(path: &str, references: &[&str]) -> ImageFile {
+        ImageFile {
+            path: path.to_string(),
+            filename: path.rsplit('/').next().unwrap().to_string(),
+            folder: String::new(),
+            timestamp: String::new(),
+            modified: 0,
+            mime_type: "image/png".to_string(),
+            metadata: Some(ImageMetadata {
+                reference_images: references.iter().map(|r| r.to_string()).collect(),
+                ..Default::default()
+            }),
+            properties: None,
+            annotation: None,
+        }
+    }
+
+    #[test]
+    fn test_lineage_walks_references_and_flags_missing() {
+        let project = "/nonexistent-project";
+        let graph = LineageGraph::build(project, vec![
+            image("/nonexistent-project/generated_images/draft.png", &[]),
+            // Relative to the project root, as the generator records it
+            image("/nonexistent-project/generated_images/v2.png", &["generated_images/draft.png", "refs/logo.png"]),
+            image("/nonexistent-project/generated_images/final/v3.png", &["../v2.png"]),
+        ]);
+
+        let lineage = graph.lineage("/nonexistent-project/generated_images/final/v3.png").unwrap();
+        assert_eq!(lineage.parents.len(), 1);
+        assert_eq!(lineage.parents[0].path, "/nonexistent-project/generated_images/v2.png");
+
+        let ancestors: Vec<(&str, usize)> = lineage.ancestors.iter().map(|a| (a.node.path.as_str(), a.depth)).collect();
+        assert_eq!(ancestors, vec![
+            ("/nonexistent-project/generated_images/v2.png", 1),
+            ("/nonexistent-project/generated_images/draft.png", 2),
+            ("/nonexistent-project/refs/logo.png", 2),
+        ]);
+        assert!(!lineage.ancestors[2].node.exists);
+
+        let draft = graph.lineage("/nonexistent-project/generated_images/draft.png").unwrap();
+        assert_eq!(draft.children.len(), 1);
+
+        let missing = graph.missing_references();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].reference, "refs/logo.png");
+    }
+}
@@ -0,0 +1,136 @@
+//! Versioned schema for the companion JSON written by generate-image.py
+//!
+//! Version history:
+//! - 1: no `schema_version` field
+//! - 2: `schema_version` written by the generator; the fields are otherwise unchanged
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Must match METADATA_SCHEMA_VERSION in generate-image.py
+pub const SCHEMA_VERSION: u32 = 2;
+
+fn legacy_version() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImageMetadata {
+    #[serde(default = "legacy_version")]
+    pub schema_version: u32,
+    pub prompt: Option<String>,
+    pub model: Option<String>,
+    pub aspect_ratio: Option<String>,
+    /// Requested output size (1K, 2K or 4K)
+    pub image_size: Option<String>,
+    #[serde(default)]
+    pub reference_images: Vec<String>,
+    /// Local time the generation finished, as written by Python's isoformat()
+    pub timestamp: Option<String>,
+    /// Text the model returned alongside the image
+    pub description: Option<String>,
+    /// Fields we don't know about, kept so rewriting a file never loses data
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Upgrade raw metadata JSON to the current schema version in place
+fn migrate(json: &mut Map<String, Value>) {
+    // 1 -> 2 only added the version field, so there is nothing to rewrite yet
+    json.insert("schema_version".to_string(), Value::from(SCHEMA_VERSION));
+}
+
+/// Parse metadata JSON of any known version, migrating it to the current schema
+pub fn parse(content: &str) -> Result<ImageMetadata, String> {
+    let mut json: Map<String, Value> = serde_json::from_str(content)
+        .map_err(|e| format!("Failed to parse metadata: {}", e))?;
+    migrate(&mut json);
+
+    serde_json::from_value(Value::Object(json))
+        .map_err(|e| format!("Invalid metadata: {}", e))
+}
+
+/// Read and migrate an image's companion JSON file
+pub fn read(json_path: &Path) -> Result<ImageMetadata, String> {
+    let content = fs::read_to_string(json_path)
+        .map_err(|e| format!("Failed to read metadata: {}", e))?;
+    parse(&content)
+}
+
+/// Look up a text chunk by keyword, ignoring case
+fn text_field(chunks: &BTreeMap<String, String>, keywords: &[&str]) -> Option<String> {
+    keywords.iter().find_map(|keyword| {
+        chunks
+            .iter()
+            .find(|(k, v)| k.eq_ignore_ascii_case(keyword) && !v.trim().is_empty())
+            .map(|(_, v)| v.trim().to_string())
+    })
+}
+
+/// Build metadata from an image's embedded text chunks, for images without a companion JSON.
+/// Returns None if none of the chunks look like generation metadata.
+pub fn from_text_chunks(chunks: &BTreeMap<String, String>) -> Option<ImageMetadata> {
+    let metadata = ImageMetadata {
+        schema_version: SCHEMA_VERSION,
+        // "parameters" is what Stable Diffusion web UIs write
+        prompt: text_field(chunks, &["prompt", "parameters"]),
+        model: text_field(chunks, &["model"]),
+        description: text_field(chunks, &["description", "comment"]),
+        ..Default::default()
+    };
+
+    if metadata.prompt.is_none() && metadata.description.is_none() {
+        return None;
+    }
+    Some(metadata)
+}
+
+/// Rewrite a companion JSON file in the current schema if it is older.
+/// Returns true if the file was upgraded.
+pub fn upgrade_file(json_path: &Path) -> Result<bool, String> {
+    let content = fs::read_to_string(json_path)
+        .map_err(|e| format!("Failed to read metadata: {}", e))?;
+    let json: Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse metadata: {}", e))?;
+
+    let version = json.get("schema_version").and_then(|v| v.as_u64());
+    if version == Some(SCHEMA_VERSION as u64) {
+        return Ok(false);
+    }
+
+    let metadata = parse(&content)?;
+    let upgraded = serde_json::to_string_pretty(&metadata)
+        .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
+    fs::write(json_path, upgraded)
+        .map_err(|e| format!("Failed to write metadata: {}", e))?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_migrates_legacy_and_keeps_unknown_fields() {
+        let legacy = r#"{
+            "prompt": "A golden door",
+            "model": "gemini-2.5-flash-image",
+            "image_size": "4K",
+            "seed": 42
+        }"#;
+
+        let metadata = parse(legacy).unwrap();
+        assert_eq!(metadata.schema_version, SCHEMA_VERSION);
+        assert_eq!(metadata.image_size.as_deref(), Some("4K"));
+        assert!(metadata.reference_images.is_empty());
+        assert_eq!(metadata.extra.get("seed"), Some(&Value::from(42)));
+
+        let round_trip = serde_json::to_value(&metadata).unwrap();
+        assert_eq!(round_trip["seed"], 42);
+        assert_eq!(round_trip["schema_version"], SCHEMA_VERSION);
+    }
+}
@@ -0,0 +1,238 @@
+//! Image properties read from the file itself
+//! Only headers are decoded, so this stays cheap enough to run while indexing
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use image::ImageDecoder;
+use serde::{Deserialize, Serialize};
+use crate::formats::ImageFormat;
+
+const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+/// Text chunks larger than this are skipped rather than read into memory
+const MAX_TEXT_CHUNK: u32 = 1024 * 1024;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImageProperties {
+    pub width: u32,
+    pub height: u32,
+    /// Bits per channel
+    pub bit_depth: Option<u8>,
+    /// Channel layout, e.g. "rgb", "rgba", "grayscale" or "indexed"
+    pub color_type: Option<String>,
+    pub has_icc_profile: bool,
+    /// PNG tEXt, zTXt and iTXt chunks by keyword
+    #[serde(default)]
+    pub text: BTreeMap<String, String>,
+}
+
+/// Read the properties of an image whose format has already been detected.
+/// Returns None for formats we can't parse headers for.
+pub fn read(path: &Path, format: ImageFormat) -> Result<Option<ImageProperties>, String> {
+    match format {
+        ImageFormat::Png => read_png(path).map(Some),
+        f if f.is_decodable() => read_with_decoder(path).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Read headers through the image crate's decoders (JPEG, WebP, GIF)
+fn read_with_decoder(path: &Path) -> Result<ImageProperties, String> {
+    let mut decoder = image::ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|e| format!("Failed to open image: {}", e))?
+        .into_decoder()
+        .map_err(|e| format!("Failed to read image header: {}", e))?;
+
+    let (width, height) = decoder.dimensions();
+    let color = decoder.color_type();
+    let has_icc_profile = decoder.icc_profile().ok().flatten().is_some();
+
+    let color_type = match (color.has_color(), color.has_alpha()) {
+        (true, true) => "rgba",
+        (true, false) => "rgb",
+        (false, true) => "grayscale_alpha",
+        (false, false) => "grayscale",
+    };
+
+    Ok(ImageProperties {
+        width,
+        height,
+        bit_depth: Some((color.bits_per_pixel() / color.channel_count() as u16) as u8),
+        color_type: Some(color_type.to_string()),
+        has_icc_profile,
+        text: BTreeMap::new(),
+    })
+}
+
+fn png_color_type(code: u8) -> Option<&'static str> {
+    match code {
+        0 => Some("grayscale"),
+        2 => Some("rgb"),
+        3 => Some("indexed"),
+        4 => Some("grayscale_alpha"),
+        6 => Some("rgba"),
+        _ => None,
+    }
+}
+
+/// Walk a PNG's chunks, reading IHDR, iCCP and text chunks and seeking past pixel data.
+/// Text chunks may come after the image data, so the whole chunk list is walked.
+fn read_png(path: &Path) -> Result<ImageProperties, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open image: {}", e))?;
+    let mut reader = BufReader::new(file);
+
+    let mut signature = [0u8; 8];
+    reader.read_exact(&mut signature)
+        .map_err(|e| format!("Failed to read image header: {}", e))?;
+    if &signature != PNG_SIGNATURE {
+        return Err("Not a PNG file".to_string());
+    }
+
+    let mut properties = ImageProperties::default();
+    let mut seen_header = false;
+
+    loop {
+        let mut chunk_header = [0u8; 8];
+        if reader.read_exact(&mut chunk_header).is_err() {
+            // Truncated file; keep whatever we've read so far
+            break;
+        }
+        let length = u32::from_be_bytes([chunk_header[0], chunk_header[1], chunk_header[2], chunk_header[3]]);
+        let chunk_type = [chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7]];
+
+        let wanted = matches!(&chunk_type, b"IHDR" | b"tEXt" | b"zTXt" | b"iTXt") && length <= MAX_TEXT_CHUNK;
+        if !wanted {
+            if &chunk_type == b"iCCP" {
+                properties.has_icc_profile = true;
+            }
+            if &chunk_type == b"IEND" {
+                break;
+            }
+            // Skip the data and CRC
+            reader.seek(SeekFrom::Current(length as i64 + 4))
+                .map_err(|e| format!("Failed to read image: {}", e))?;
+            continue;
+        }
+
+        let mut data = vec![0u8; length as usize + 4];
+        if reader.read_exact(&mut data).is_err() {
+            break;
+        }
+        data.truncate(length as usize);
+
+        match &chunk_type {
+            b"IHDR" if data.len() >= 10 => {
+                properties.width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+                properties.height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+                properties.bit_depth = Some(data[8]);
+                properties.color_type = png_color_type(data[9]).map(str::to_string);
+                seen_header = true;
+            }
+            b"tEXt" => insert_text(&mut properties.text, parse_text(&data)),
+            b"zTXt" => insert_text(&mut properties.text, parse_ztxt(&data)),
+            b"iTXt" => insert_text(&mut properties.text, parse_itxt(&data)),
+            _ => {}
+        }
+    }
+
+    if !seen_header {
+        return Err("PNG is missing its IHDR chunk".to_string());
+    }
+    Ok(properties)
+}
+
+fn insert_text(text: &mut BTreeMap<String, String>, entry: Option<(String, String)>) {
+    if let Some((keyword, value)) = entry {
+        text.insert(keyword, value);
+    }
+}
+
+/// tEXt and zTXt keywords and text are Latin-1
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+fn split_null(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let null = data.iter().position(|&b| b == 0)?;
+    Some((&data[..null], &data[null + 1..]))
+}
+
+fn inflate(data: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::new();
+    flate2::read::ZlibDecoder::new(data)
+        .take(MAX_TEXT_CHUNK as u64)
+        .read_to_end(&mut decoded)
+        .ok()?;
+    Some(decoded)
+}
+
+/// keyword \0 text
+fn parse_text(data: &[u8]) -> Option<(String, String)> {
+    let (keyword, text) = split_null(data)?;
+    Some((latin1(keyword), latin1(text)))
+}
+
+/// keyword \0 method compressed-text
+fn parse_ztxt(data: &[u8]) -> Option<(String, String)> {
+    let (keyword, rest) = split_null(data)?;
+    let (_method, compressed) = rest.split_first()?;
+    Some((latin1(keyword), latin1(&inflate(compressed)?)))
+}
+
+/// keyword \0 compressed-flag method language \0 translated-keyword \0 text
+fn parse_itxt(data: &[u8]) -> Option<(String, String)> {
+    let (keyword, rest) = split_null(data)?;
+    if rest.len() < 2 {
+        return None;
+    }
+    let compressed = rest[0] == 1;
+    let (_language, rest) = split_null(&rest[2..])?;
+    let (_translated, text) = split_null(rest)?;
+
+    let text = if compressed { inflate(text)? } else { text.to_vec() };
+    Some((latin1(keyword), String::from_utf8(text).ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut bytes = (data.len() as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(kind);
+        bytes.extend_from_slice(data);
+        bytes.extend_from_slice(&[0, 0, 0, 0]); // CRC isn't checked
+        bytes
+    }
+
+    #[test]
+    fn test_read_png_header_and_text_chunks() {
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&4096u32.to_be_bytes());
+        ihdr.extend_from_slice(&2304u32.to_be_bytes());
+        ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend(chunk(b"IHDR", &ihdr));
+        png.extend(chunk(b"iCCP", b"sRGB\0\0"));
+        png.extend(chunk(b"tEXt", b"prompt\0A golden door"));
+        png.extend(chunk(b"IDAT", &[0; 32]));
+        png.extend(chunk(b"iTXt", "Description\0\0\0\0\0Caf\u{e9}".as_bytes()));
+        png.extend(chunk(b"IEND", &[]));
+
+        let path = std::env::temp_dir().join(format!("properties-test-{}.png", uuid::Uuid::new_v4()));
+        std::fs::write(&path, &png).unwrap();
+        let properties = read(&path, ImageFormat::Png).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((properties.width, properties.height), (4096, 2304));
+        assert_eq!(properties.bit_depth, Some(8));
+        assert_eq!(properties.color_type.as_deref(), Some("rgba"));
+        assert!(properties.has_icc_profile);
+        assert_eq!(properties.text.get("prompt").map(String::as_str), Some("A golden door"));
+        assert_eq!(properties.text.get("Description").map(String::as_str), Some("Caf\u{e9}"));
+    }
+}
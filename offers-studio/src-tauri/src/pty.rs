@@ -1,11 +1,145 @@
-use portable_pty::{native_pty_system, CommandBuilder, PtySize};
-use std::collections::HashMap;
+use portable_pty::{native_pty_system, ChildKiller, CommandBuilder, PtySize};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
 use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use tauri::{AppHandle, Emitter};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use crate::pty_output::{self, Credits, OutputMetrics, OutputMetricsSnapshot};
+use crate::sessions;
+use crate::scrollback::{Scrollback, ScrollbackSnapshot, SCROLLBACK_LIMIT};
 
 type PtyId = String;
+type Killer = Box<dyn ChildKiller + Send + Sync>;
+
+/// How long to wait after the child exits for the reader to flush its last output
+const READER_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Exited PTYs kept around so `list_ptys` and scrollback still work for them
+const EXITED_HISTORY: usize = 16;
+
+/// Payload of the `pty-data:{id}` event
+#[derive(Debug, Clone, Serialize)]
+pub struct PtyData {
+    pub data: String,
+    /// Byte offset of the end of `data` in the PTY's whole output. A terminal that
+    /// reattached from a scrollback snapshot can drop events at or below its offset.
+    pub offset: u64,
+}
+
+/// Payload of the `pty-exit:{id}` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PtyExit {
+    pub success: bool,
+    /// None if the child was killed by a signal or couldn't be waited on
+    pub exit_code: Option<u32>,
+    /// Signal description, e.g. "Hangup"
+    pub signal: Option<String>,
+}
+
+impl From<&portable_pty::ExitStatus> for PtyExit {
+    fn from(status: &portable_pty::ExitStatus) -> Self {
+        // portable_pty only exposes the signal through Display
+        let signal = status
+            .to_string()
+            .strip_prefix("Terminated by ")
+            .map(|s| s.to_string());
+        Self {
+            success: status.success(),
+            exit_code: signal.is_none().then(|| status.exit_code()),
+            signal,
+        }
+    }
+}
+
+/// Which step of `kill` ended the PTY's process group
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KillStage {
+    /// Nothing was left running to signal
+    AlreadyExited,
+    Hangup,
+    Terminate,
+    Kill,
+}
+
+/// Signals sent to the process group in turn, and how long each gets before escalating
+#[cfg(unix)]
+const KILL_ESCALATION: [(KillStage, libc::c_int, Duration); 3] = [
+    (KillStage::Hangup, libc::SIGHUP, Duration::from_secs(2)),
+    (KillStage::Terminate, libc::SIGTERM, Duration::from_secs(3)),
+    (KillStage::Kill, libc::SIGKILL, Duration::from_secs(1)),
+];
+
+/// Shorter timeouts for quitting, so closing the window doesn't hang
+#[cfg(unix)]
+const SHUTDOWN_ESCALATION: [(KillStage, libc::c_int, Duration); 3] = [
+    (KillStage::Hangup, libc::SIGHUP, Duration::from_millis(500)),
+    (KillStage::Terminate, libc::SIGTERM, Duration::from_millis(500)),
+    (KillStage::Kill, libc::SIGKILL, Duration::from_millis(250)),
+];
+
+#[cfg(unix)]
+const KILL_POLL_INTERVAL: Duration = Duration::from_millis(25);
+
+/// Whether any process in the group is still around
+#[cfg(unix)]
+fn group_alive(pgid: libc::pid_t) -> bool {
+    // Signal 0 only checks for existence; EPERM still means something is there
+    let result = unsafe { libc::killpg(pgid, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+/// Signal a process group with increasing force until it's gone.
+/// The PTY child is a session leader, so its pid is also its process group id,
+/// and subprocesses it started (like `uv run generate-image.py`) are in the group too.
+#[cfg(unix)]
+fn terminate_group(
+    pgid: libc::pid_t,
+    escalation: &[(KillStage, libc::c_int, Duration)],
+) -> Result<KillStage, String> {
+    if !group_alive(pgid) {
+        return Ok(KillStage::AlreadyExited);
+    }
+
+    for &(stage, signal, timeout) in escalation {
+        unsafe { libc::killpg(pgid, signal) };
+        let deadline = Instant::now() + timeout;
+        loop {
+            if !group_alive(pgid) {
+                return Ok(stage);
+            }
+            if Instant::now() >= deadline {
+                break;
+            }
+            thread::sleep(KILL_POLL_INTERVAL);
+        }
+    }
+
+    Err(format!("Process group {} still running after SIGKILL", pgid))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum PtyStatus {
+    Running,
+    Exited(PtyExit),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PtyInfo {
+    pub id: PtyId,
+    pub command: String,
+    pub args: Vec<String>,
+    pub cwd: String,
+    pub pid: Option<u32>,
+    pub started_at: String,
+    pub status: PtyStatus,
+}
 
 /// Find the last position in a byte slice that ends on a valid UTF-8 boundary.
 /// This prevents splitting multi-byte UTF-8 characters across emissions.
@@ -49,20 +183,137 @@ fn find_utf8_boundary(bytes: &[u8]) -> usize {
     0
 }
 
+/// A terminal that was running when the app quit, saved so it can be restored
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedTerminal {
+    pub info: PtyInfo,
+    /// The Claude Code session it was running, to resume with `claude --resume`.
+    /// None if it couldn't be told apart from another terminal's session.
+    pub session_id: Option<String>,
+    pub scrollback: String,
+}
+
+/// The session a PTY was explicitly told to resume
+fn resumed_session(info: &PtyInfo) -> Option<String> {
+    info.args
+        .windows(2)
+        .find(|pair| pair[0] == "--resume" || pair[0] == "-r")
+        .map(|pair| pair[1].clone())
+}
+
+/// Whether a PTY is a plain `claude` whose session has to be inferred
+fn is_fresh_claude(info: &PtyInfo) -> bool {
+    Path::new(&info.command).file_name().is_some_and(|name| name == "claude")
+        && resumed_session(info).is_none()
+}
+
+/// Work out which Claude Code session each PTY was running. Resumed sessions are
+/// known from the arguments. For a plain `claude` the session is the one written
+/// to since it started, but only when that's unambiguous: with two such terminals
+/// in one project, or several candidate sessions, guessing could restore a terminal
+/// into another's conversation, so those are left as None.
+fn session_ids(infos: &[PtyInfo]) -> Vec<Option<String>> {
+    let resumed: Vec<Option<String>> = infos.iter().map(resumed_session).collect();
+
+    infos
+        .iter()
+        .zip(&resumed)
+        .map(|(info, resumed_id)| {
+            if resumed_id.is_some() || !is_fresh_claude(info) {
+                return resumed_id.clone();
+            }
+            let sharing_project = infos
+                .iter()
+                .filter(|other| other.cwd == info.cwd && is_fresh_claude(other))
+                .count();
+            if sharing_project > 1 {
+                return None;
+            }
+
+            let started = chrono::DateTime::parse_from_rfc3339(&info.started_at).ok()?;
+            let candidates: Vec<String> = sessions::sessions_since(&info.cwd, started.into())
+                .into_iter()
+                .filter(|id| !resumed.iter().flatten().any(|resumed_id| resumed_id == id))
+                .collect();
+            match candidates.as_slice() {
+                [only] => Some(only.clone()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Get the file terminals are saved to on quit
+fn get_saved_terminals_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join("terminals.json"))
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+/// Save terminals for the next launch, replacing whatever was saved before
+pub fn save_terminals(app: &AppHandle, terminals: &[SavedTerminal]) -> Result<(), String> {
+    let path = get_saved_terminals_path(app)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    }
+    let content = serde_json::to_string(terminals)
+        .map_err(|e| format!("Failed to serialize terminals: {}", e))?;
+
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, content)
+        .map_err(|e| format!("Failed to write terminals: {}", e))?;
+    fs::rename(&tmp_path, &path)
+        .map_err(|e| format!("Failed to replace terminals: {}", e))
+}
+
+/// Terminals that were running when the app last quit
+pub fn load_saved_terminals(app: &AppHandle) -> Result<Vec<SavedTerminal>, String> {
+    let path = get_saved_terminals_path(app)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read terminals: {}", e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse terminals: {}", e))
+}
+
+/// Cheap to clone; every clone shares the same PTYs
+#[derive(Clone)]
 pub struct PtyManager {
     ptys: Arc<Mutex<HashMap<PtyId, PtyHandle>>>,
+    /// Most recently exited last
+    exited: Arc<Mutex<VecDeque<ExitedPty>>>,
+}
+
+struct ExitedPty {
+    info: PtyInfo,
+    scrollback: Arc<Mutex<Scrollback>>,
+    metrics: Arc<OutputMetrics>,
 }
 
 struct PtyHandle {
     #[allow(dead_code)]
     master: Box<dyn portable_pty::MasterPty + Send>,
-    writer: Box<dyn Write + Send>,
+    /// Locked on its own, so a write blocked on a busy child doesn't hold up other PTYs
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    /// Recent output, shared with the reader thread
+    scrollback: Arc<Mutex<Scrollback>>,
+    metrics: Arc<OutputMetrics>,
+    /// Released as the terminal acks batches of output
+    credits: Arc<Credits>,
+    /// Used where there's no process group to signal
+    killer: Killer,
+    info: PtyInfo,
 }
 
 impl PtyManager {
     pub fn new() -> Self {
         Self {
             ptys: Arc::new(Mutex::new(HashMap::new())),
+            exited: Arc::new(Mutex::new(VecDeque::new())),
         }
     }
 
@@ -88,9 +339,9 @@ impl PtyManager {
             .map_err(|e| format!("Failed to open PTY: {}", e))?;
 
         // Build command
-        let mut cmd = CommandBuilder::new(command);
-        cmd.args(args);
-        cmd.cwd(cwd);
+        let mut cmd = CommandBuilder::new(&command);
+        cmd.args(&args);
+        cmd.cwd(&cwd);
 
         // Set terminal environment variables for proper escape sequence handling
         cmd.env("TERM", "xterm-256color");
@@ -116,6 +367,21 @@ impl PtyManager {
         // Generate unique ID
         let pty_id = uuid::Uuid::new_v4().to_string();
         let pty_id_clone = pty_id.clone();
+        let scrollback = Arc::new(Mutex::new(Scrollback::new(SCROLLBACK_LIMIT)));
+        let scrollback_clone = scrollback.clone();
+        let metrics = Arc::new(OutputMetrics::default());
+        let metrics_clone = metrics.clone();
+        let credits = Arc::new(Credits::default());
+        let credits_clone = credits.clone();
+        let info = PtyInfo {
+            id: pty_id.clone(),
+            command,
+            args,
+            cwd,
+            pid: child.process_id(),
+            started_at: chrono::Local::now().to_rfc3339(),
+            status: PtyStatus::Running,
+        };
 
         // Store PTY handle
         {
@@ -124,13 +390,33 @@ impl PtyManager {
                 pty_id.clone(),
                 PtyHandle {
                     master: pair.master,
-                    writer,
+                    writer: Arc::new(Mutex::new(writer)),
+                    scrollback,
+                    metrics,
+                    credits,
+                    killer: child.clone_killer(),
+                    info,
                 },
             );
         }
 
-        // Spawn thread to read PTY output and emit events
+        // Reader and emitter threads, joined by a bounded queue: the reader blocks
+        // when the webview falls behind, and the emitter batches output into events
         let app_clone = app.clone();
+        let (sender, output_rx) = pty_output::queue(metrics_clone.clone());
+        let (reader_done, reader_done_rx) = mpsc::channel::<()>();
+        thread::spawn(move || {
+            // Dropped once all output has been emitted, which wakes the child monitor
+            let _reader_done = reader_done;
+            let event = format!("pty-data:{}", pty_id_clone);
+            pty_output::run_emitter(output_rx, &metrics_clone, &credits_clone, |data| {
+                // Pushed and numbered under the lock, so an event is never split
+                // across a snapshot: it's either wholly in it or wholly after it
+                let offset = scrollback_clone.lock().unwrap().push(&data);
+                app_clone.emit(&event, PtyData { data, offset }).is_ok()
+            });
+        });
+
         thread::spawn(move || {
             let mut buf = [0u8; 8192];
             let mut pending_bytes: Vec<u8> = Vec::new();
@@ -141,9 +427,8 @@ impl PtyManager {
                         // EOF - flush any remaining bytes and exit
                         if !pending_bytes.is_empty() {
                             let data = String::from_utf8_lossy(&pending_bytes).to_string();
-                            let _ = app_clone.emit(&format!("pty-data:{}", pty_id_clone), data);
+                            sender.send(data);
                         }
-                        let _ = app_clone.emit(&format!("pty-exit:{}", pty_id_clone), ());
                         break;
                     }
                     Ok(n) => {
@@ -154,10 +439,12 @@ impl PtyManager {
                         let valid_up_to = find_utf8_boundary(&pending_bytes);
 
                         if valid_up_to > 0 {
-                            // Extract valid UTF-8 portion and emit
+                            // Extract valid UTF-8 portion and queue it
                             let valid_bytes: Vec<u8> = pending_bytes.drain(..valid_up_to).collect();
                             if let Ok(data) = String::from_utf8(valid_bytes) {
-                                let _ = app_clone.emit(&format!("pty-data:{}", pty_id_clone), data);
+                                if !sender.send(data) {
+                                    break;
+                                }
                             }
                         }
                     }
@@ -169,27 +456,58 @@ impl PtyManager {
             }
         });
 
-        // Spawn thread to monitor child process
+        // Spawn thread to monitor child process, report how it exited and reap the handle
+        let ptys = self.ptys.clone();
+        let exited = self.exited.clone();
+        let exit_id = pty_id.clone();
         thread::spawn(move || {
-            let _ = child.wait();
+            let exit = match child.wait() {
+                Ok(status) => PtyExit::from(&status),
+                Err(e) => {
+                    eprintln!("PTY wait error: {}", e);
+                    PtyExit { success: false, exit_code: None, signal: None }
+                }
+            };
+
+            // Let the reader emit the last of the output before announcing the exit.
+            // It may never finish if a grandchild still holds the terminal open.
+            let _ = reader_done_rx.recv_timeout(READER_DRAIN_TIMEOUT);
+
+            let handle = ptys.lock().unwrap().remove(&exit_id);
+            if let Some(handle) = handle {
+                let mut info = handle.info;
+                info.status = PtyStatus::Exited(exit.clone());
+                let mut exited = exited.lock().unwrap();
+                exited.push_back(ExitedPty {
+                    info,
+                    scrollback: handle.scrollback,
+                    metrics: handle.metrics,
+                });
+                while exited.len() > EXITED_HISTORY {
+                    exited.pop_front();
+                }
+            }
+
+            let _ = app.emit(&format!("pty-exit:{}", exit_id), exit);
         });
 
         Ok(pty_id)
     }
 
+    /// Send input to a PTY. This blocks while the child isn't reading its input,
+    /// e.g. while it's stalled on output the terminal hasn't caught up with.
     pub fn write(&self, pty_id: &str, data: &str) -> Result<(), String> {
-        let mut ptys = self.ptys.lock().unwrap();
-        if let Some(pty) = ptys.get_mut(pty_id) {
-            pty.writer
-                .write_all(data.as_bytes())
-                .map_err(|e| format!("Failed to write to PTY: {}", e))?;
-            pty.writer
-                .flush()
-                .map_err(|e| format!("Failed to flush PTY: {}", e))?;
-            Ok(())
-        } else {
-            Err(format!("PTY not found: {}", pty_id))
-        }
+        let writer = match self.ptys.lock().unwrap().get(pty_id) {
+            Some(pty) => pty.writer.clone(),
+            None => return Err(format!("PTY not found: {}", pty_id)),
+        };
+        let mut writer = writer.lock().unwrap();
+        writer
+            .write_all(data.as_bytes())
+            .map_err(|e| format!("Failed to write to PTY: {}", e))?;
+        writer
+            .flush()
+            .map_err(|e| format!("Failed to flush PTY: {}", e))
     }
 
     pub fn resize(&self, pty_id: &str, cols: u16, rows: u16) -> Result<(), String> {
@@ -209,12 +527,160 @@ impl PtyManager {
         }
     }
 
-    pub fn kill(&self, pty_id: &str) -> Result<(), String> {
-        let mut ptys = self.ptys.lock().unwrap();
-        if ptys.remove(pty_id).is_some() {
-            Ok(())
-        } else {
-            Err(format!("PTY not found: {}", pty_id))
+    /// Output retained for a PTY, or only what came after `since`, so a reloaded
+    /// terminal can redraw. Listen first, then drop `pty-data` events whose offset
+    /// is at or below the snapshot's, since they're already in it.
+    pub fn scrollback(&self, pty_id: &str, since: Option<u64>) -> Result<ScrollbackSnapshot, String> {
+        let scrollback = match self.ptys.lock().unwrap().get(pty_id) {
+            Some(pty) => pty.scrollback.clone(),
+            None => self.exited
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|pty| pty.info.id == pty_id)
+                .map(|pty| pty.scrollback.clone())
+                .ok_or_else(|| format!("PTY not found: {}", pty_id))?,
+        };
+        let snapshot = scrollback.lock().unwrap().snapshot(since);
+        Ok(snapshot)
+    }
+
+    /// The terminal has processed a batch of output, so another can be sent
+    pub fn ack(&self, pty_id: &str) -> Result<(), String> {
+        let credits = match self.ptys.lock().unwrap().get(pty_id) {
+            Some(pty) => pty.credits.clone(),
+            None => return Err(format!("PTY not found: {}", pty_id)),
+        };
+        credits.ack();
+        Ok(())
+    }
+
+    /// Output throughput, backpressure and emit latency for a PTY
+    pub fn metrics(&self, pty_id: &str) -> Result<OutputMetricsSnapshot, String> {
+        if let Some(pty) = self.ptys.lock().unwrap().get(pty_id) {
+            return Ok(pty.metrics.snapshot());
         }
+        self.exited
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|pty| pty.info.id == pty_id)
+            .map(|pty| pty.metrics.snapshot())
+            .ok_or_else(|| format!("PTY not found: {}", pty_id))
+    }
+
+    /// Terminate every running PTY, giving them a moment to exit cleanly, and
+    /// return what's needed to restore them next launch
+    pub fn shutdown(&self) -> Vec<SavedTerminal> {
+        let running: Vec<(PtyInfo, Arc<Mutex<Scrollback>>, Killer)> = self.ptys
+            .lock()
+            .unwrap()
+            .values()
+            .map(|pty| (pty.info.clone(), pty.scrollback.clone(), pty.killer.clone_killer()))
+            .collect();
+
+        // In parallel, so quitting takes as long as the slowest PTY rather than all of them
+        thread::scope(|scope| {
+            for (info, _, killer) in &running {
+                let mut killer = killer.clone_killer();
+                scope.spawn(move || {
+                    #[cfg(unix)]
+                    if let Some(pid) = info.pid {
+                        if let Err(e) = terminate_group(pid as libc::pid_t, &SHUTDOWN_ESCALATION) {
+                            eprintln!("Failed to terminate PTY {}: {}", info.id, e);
+                        }
+                        return;
+                    }
+                    #[cfg(not(unix))]
+                    let _ = info;
+                    let _ = killer.kill();
+                });
+            }
+        });
+
+        // Dropping the handles hangs up anything that's left
+        self.ptys.lock().unwrap().clear();
+
+        let infos: Vec<PtyInfo> = running.iter().map(|(info, _, _)| info.clone()).collect();
+        running
+            .into_iter()
+            .zip(session_ids(&infos))
+            .map(|((info, scrollback, _), session_id)| SavedTerminal {
+                session_id,
+                scrollback: scrollback.lock().unwrap().snapshot(None).data,
+                info,
+            })
+            .collect()
+    }
+
+    /// Running PTYs and those that exited recently, oldest first
+    pub fn list(&self) -> Vec<PtyInfo> {
+        let mut ptys: Vec<PtyInfo> = self.ptys
+            .lock()
+            .unwrap()
+            .values()
+            .map(|pty| pty.info.clone())
+            .collect();
+        ptys.extend(self.exited.lock().unwrap().iter().map(|pty| pty.info.clone()));
+        ptys.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+        ptys
+    }
+
+    /// Terminate a PTY's child and everything it started: SIGHUP, then SIGTERM,
+    /// then SIGKILL to the whole process group, each after a timeout.
+    /// Returns the stage that ended it; the exit itself is reported by `pty-exit`.
+    pub fn kill(&self, pty_id: &str) -> Result<KillStage, String> {
+        let (pid, mut killer) = {
+            let ptys = self.ptys.lock().unwrap();
+            let pty = ptys
+                .get(pty_id)
+                .ok_or_else(|| format!("PTY not found: {}", pty_id))?;
+            (pty.info.pid, pty.killer.clone_killer())
+        };
+
+        #[cfg(unix)]
+        if let Some(pid) = pid {
+            return terminate_group(pid as libc::pid_t, &KILL_ESCALATION);
+        }
+
+        // No process group to signal: ask the child to stop (TerminateProcess on
+        // Windows) and hang up the terminal by dropping the master
+        #[cfg(not(unix))]
+        let _ = pid;
+        killer
+            .kill()
+            .map_err(|e| format!("Failed to kill PTY child: {}", e))?;
+        self.ptys.lock().unwrap().remove(pty_id);
+        Ok(if cfg!(unix) { KillStage::Hangup } else { KillStage::Kill })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(cwd: &str, args: &[&str]) -> PtyInfo {
+        PtyInfo {
+            id: uuid::Uuid::new_v4().to_string(),
+            command: "claude".to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            cwd: cwd.to_string(),
+            pid: None,
+            started_at: "2026-01-01T00:00:00+00:00".to_string(),
+            status: PtyStatus::Running,
+        }
+    }
+
+    #[test]
+    fn test_session_ids_only_when_unambiguous() {
+        let ids = session_ids(&[
+            info("/nonexistent-project", &["--resume", "abc"]),
+            // Two plain terminals in one project can't be told apart
+            info("/nonexistent-project", &[]),
+            info("/nonexistent-project", &[]),
+            // No session file to pick up
+            info("/nonexistent-other", &[]),
+        ]);
+        assert_eq!(ids, vec![Some("abc".to_string()), None, None, None]);
     }
 }
@@ -0,0 +1,253 @@
+//! Batching of PTY output into events, with flow control from the webview
+//! The reader hands chunks over a bounded queue and the emitter coalesces whatever
+//! arrives within a few milliseconds into one event. Emitting only queues the event,
+//! so the terminal acks each batch once xterm has processed it, and the emitter stops
+//! after a few unacked batches. The queue then fills, the reader stops reading and the
+//! child blocks writing to the terminal, instead of output piling up or flooding IPC.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+/// How long the emitter waits for more output before sending a batch
+pub const BATCH_WINDOW: Duration = Duration::from_millis(4);
+
+/// A batch is sent straight away once it reaches this size
+pub const BATCH_MAX_BYTES: usize = 64 * 1024;
+
+/// Chunks (up to 8 KB each) queued between the reader and the emitter
+pub const QUEUE_CHUNKS: usize = 64;
+
+/// Batches sent to the webview but not yet acked before the emitter waits
+pub const MAX_IN_FLIGHT: usize = 4;
+
+/// How long to wait for an ack before assuming the terminal has gone away
+/// (e.g. the webview reloaded) and carrying on, so a detached PTY isn't stuck forever
+pub const ACK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Batches that took longer than this from read to emit count as delayed
+const DELAYED_THRESHOLD: Duration = Duration::from_millis(50);
+
+/// Counters for one PTY's output, updated from the reader and emitter threads
+#[derive(Default)]
+pub struct OutputMetrics {
+    bytes_read: AtomicU64,
+    chunks_read: AtomicU64,
+    events_emitted: AtomicU64,
+    dropped_events: AtomicU64,
+    backpressure_stalls: AtomicU64,
+    stalled_micros: AtomicU64,
+    ack_waits: AtomicU64,
+    ack_wait_micros: AtomicU64,
+    delayed_events: AtomicU64,
+    max_latency_micros: AtomicU64,
+    max_batch_bytes: AtomicU64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OutputMetricsSnapshot {
+    pub bytes_read: u64,
+    pub chunks_read: u64,
+    pub events_emitted: u64,
+    /// Batches that failed to send, or were never acked before the timeout
+    pub dropped_events: u64,
+    /// Times the reader found the queue full and had to wait
+    pub backpressure_stalls: u64,
+    pub stalled_ms: u64,
+    /// Times the emitter had to wait for the webview to ack earlier batches
+    pub ack_waits: u64,
+    pub ack_wait_ms: u64,
+    /// Batches that took longer than 50ms from read to emit
+    pub delayed_events: u64,
+    pub max_latency_ms: u64,
+    pub max_batch_bytes: u64,
+}
+
+impl OutputMetrics {
+    pub fn snapshot(&self) -> OutputMetricsSnapshot {
+        let get = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        OutputMetricsSnapshot {
+            bytes_read: get(&self.bytes_read),
+            chunks_read: get(&self.chunks_read),
+            events_emitted: get(&self.events_emitted),
+            dropped_events: get(&self.dropped_events),
+            backpressure_stalls: get(&self.backpressure_stalls),
+            stalled_ms: get(&self.stalled_micros) / 1000,
+            ack_waits: get(&self.ack_waits),
+            ack_wait_ms: get(&self.ack_wait_micros) / 1000,
+            delayed_events: get(&self.delayed_events),
+            max_latency_ms: get(&self.max_latency_micros) / 1000,
+            max_batch_bytes: get(&self.max_batch_bytes),
+        }
+    }
+}
+
+/// Batches in flight to the webview, released as the terminal acks them
+pub struct Credits {
+    in_flight: Mutex<usize>,
+    acked: Condvar,
+    max_in_flight: usize,
+    ack_timeout: Duration,
+}
+
+impl Credits {
+    pub fn new(max_in_flight: usize, ack_timeout: Duration) -> Self {
+        Self {
+            in_flight: Mutex::new(0),
+            acked: Condvar::new(),
+            max_in_flight,
+            ack_timeout,
+        }
+    }
+
+    /// The terminal has processed a batch
+    pub fn ack(&self) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        *in_flight = in_flight.saturating_sub(1);
+        self.acked.notify_all();
+    }
+
+    /// Take a credit for one more batch, waiting while too many are unacked.
+    /// Returns how many batches were given up on because their acks timed out.
+    fn acquire(&self) -> usize {
+        let in_flight = self.in_flight.lock().unwrap();
+        let (mut in_flight, wait) = self.acked
+            .wait_timeout_while(in_flight, self.ack_timeout, |n| *n >= self.max_in_flight)
+            .unwrap();
+        let abandoned = if wait.timed_out() { std::mem::take(&mut *in_flight) } else { 0 };
+        *in_flight += 1;
+        abandoned
+    }
+}
+
+impl Default for Credits {
+    fn default() -> Self {
+        Self::new(MAX_IN_FLIGHT, ACK_TIMEOUT)
+    }
+}
+
+/// Reader side of the queue
+pub struct OutputSender {
+    tx: SyncSender<(Instant, String)>,
+    metrics: Arc<OutputMetrics>,
+}
+
+/// Create the queue between a PTY's reader and emitter
+pub fn queue(metrics: Arc<OutputMetrics>) -> (OutputSender, Receiver<(Instant, String)>) {
+    let (tx, rx) = mpsc::sync_channel(QUEUE_CHUNKS);
+    (OutputSender { tx, metrics }, rx)
+}
+
+impl OutputSender {
+    /// Queue a chunk, blocking while the queue is full.
+    /// Returns false once the emitter has gone away.
+    pub fn send(&self, data: String) -> bool {
+        self.metrics.bytes_read.fetch_add(data.len() as u64, Ordering::Relaxed);
+        self.metrics.chunks_read.fetch_add(1, Ordering::Relaxed);
+
+        match self.tx.try_send((Instant::now(), data)) {
+            Ok(()) => true,
+            Err(TrySendError::Full(item)) => {
+                let started = Instant::now();
+                let sent = self.tx.send(item).is_ok();
+                self.metrics.backpressure_stalls.fetch_add(1, Ordering::Relaxed);
+                self.metrics.stalled_micros.fetch_add(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+                sent
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        }
+    }
+}
+
+/// Coalesce queued chunks into batches and hand each to `emit`, which returns
+/// whether it was sent, waiting for credits so the webview sets the pace.
+/// Runs until the reader side is dropped and drained.
+pub fn run_emitter(
+    rx: Receiver<(Instant, String)>,
+    metrics: &OutputMetrics,
+    credits: &Credits,
+    mut emit: impl FnMut(String) -> bool,
+) {
+    while let Ok((read_at, mut batch)) = rx.recv() {
+        let deadline = Instant::now() + BATCH_WINDOW;
+        while batch.len() < BATCH_MAX_BYTES {
+            match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok((_, data)) => batch.push_str(&data),
+                Err(_) => break,
+            }
+        }
+
+        let waiting = Instant::now();
+        let abandoned = credits.acquire();
+        let waited = waiting.elapsed();
+        if waited >= Duration::from_millis(1) {
+            metrics.ack_waits.fetch_add(1, Ordering::Relaxed);
+            metrics.ack_wait_micros.fetch_add(waited.as_micros() as u64, Ordering::Relaxed);
+        }
+        metrics.dropped_events.fetch_add(abandoned as u64, Ordering::Relaxed);
+
+        let latency = read_at.elapsed();
+        metrics.max_latency_micros.fetch_max(latency.as_micros() as u64, Ordering::Relaxed);
+        metrics.max_batch_bytes.fetch_max(batch.len() as u64, Ordering::Relaxed);
+        if latency > DELAYED_THRESHOLD {
+            metrics.delayed_events.fetch_add(1, Ordering::Relaxed);
+        }
+
+        if emit(batch) {
+            metrics.events_emitted.fetch_add(1, Ordering::Relaxed);
+        } else {
+            // Never reaches the webview, so it'll never be acked
+            credits.ack();
+            metrics.dropped_events.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_chunks_are_coalesced_and_counted() {
+        let metrics = Arc::new(OutputMetrics::default());
+        let (sender, rx) = queue(metrics.clone());
+
+        // Queued before the emitter starts, so they all land in one window
+        for chunk in ["one ", "two ", "three"] {
+            assert!(sender.send(chunk.to_string()));
+        }
+        drop(sender);
+
+        let emitter_metrics = metrics.clone();
+        let batches = thread::spawn(move || {
+            let credits = Credits::default();
+            let mut batches = Vec::new();
+            run_emitter(rx, &emitter_metrics, &credits, |batch| {
+                batches.push(batch);
+                credits.ack();
+                true
+            });
+            batches
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(batches, vec!["one two three"]);
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.chunks_read, 3);
+        assert_eq!(snapshot.bytes_read, 13);
+        assert_eq!(snapshot.events_emitted, 1);
+        assert_eq!(snapshot.max_batch_bytes, 13);
+        assert_eq!(snapshot.dropped_events, 0);
+
+        // Without acks the emitter waits, then gives up on the unacked batch
+        let credits = Credits::new(1, Duration::from_millis(10));
+        assert_eq!(credits.acquire(), 0);
+        assert_eq!(credits.acquire(), 1);
+        credits.ack();
+        assert_eq!(credits.acquire(), 0);
+    }
+}
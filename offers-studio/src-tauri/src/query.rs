@@ -0,0 +1,289 @@
+//! Sorted, filtered and paginated queries over the image catalog
+//! Lets the gallery virtualize large libraries instead of loading every image
+
+use std::cmp::Ordering;
+use serde::{Deserialize, Serialize};
+use crate::catalog::ImageFile;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    /// File modification time
+    #[default]
+    Mtime,
+    /// The `timestamp` recorded by the generator in the companion JSON
+    MetadataTimestamp,
+    Model,
+    /// Width divided by height, so portrait sorts before square before landscape
+    AspectRatio,
+    /// Star rating; unrated images sort last
+    Rating,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Query parameters; every field is optional so the frontend only sends what it uses
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ImageQuery {
+    pub sort: SortKey,
+    pub direction: SortDirection,
+    pub offset: usize,
+    /// Only images in this folder (relative to generated_images, "" for the top level)
+    pub folder: Option<String>,
+    /// With `folder`, also include images in its subfolders
+    pub include_subfolders: bool,
+    /// Maximum images to return; None returns everything after `offset`
+    pub limit: Option<usize>,
+    pub model: Option<String>,
+    pub aspect_ratio: Option<String>,
+    pub image_size: Option<String>,
+    /// Only images modified at or after this time (ms since epoch)
+    pub from: Option<i64>,
+    /// Only images modified at or before this time (ms since epoch)
+    pub to: Option<i64>,
+    /// Only images that did (true) or did not (false) use reference images
+    pub has_references: Option<bool>,
+    /// Only images carrying every one of these tags
+    pub tags: Vec<String>,
+    /// Only images rated at least this many stars
+    pub min_rating: Option<u8>,
+    /// Only favourites (true) or non-favourites (false)
+    pub favorite: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImagePage {
+    pub images: Vec<ImageFile>,
+    /// Number of images matching the filters, before pagination
+    pub total: usize,
+    pub offset: usize,
+}
+
+fn has_references(image: &ImageFile) -> bool {
+    image.metadata
+        .as_ref()
+        .is_some_and(|m| !m.reference_images.is_empty())
+}
+
+/// Check an optional metadata field against an optional filter value
+fn field_matches(value: Option<&str>, filter: &Option<String>) -> bool {
+    match filter {
+        Some(expected) => value.is_some_and(|v| v.eq_ignore_ascii_case(expected)),
+        None => true,
+    }
+}
+
+impl ImageQuery {
+    fn in_folder(&self, image: &ImageFile) -> bool {
+        match &self.folder {
+            None => true,
+            Some(folder) if image.folder == *folder => true,
+            Some(folder) => {
+                self.include_subfolders
+                    && (folder.is_empty() || image.folder.starts_with(&format!("{}/", folder)))
+            }
+        }
+    }
+
+    fn matches(&self, image: &ImageFile) -> bool {
+        let meta = image.metadata.as_ref();
+
+        self.in_folder(image)
+            && field_matches(meta.and_then(|m| m.model.as_deref()), &self.model)
+            && field_matches(meta.and_then(|m| m.aspect_ratio.as_deref()), &self.aspect_ratio)
+            && field_matches(meta.and_then(|m| m.image_size.as_deref()), &self.image_size)
+            && self.from.is_none_or(|from| image.modified >= from)
+            && self.to.is_none_or(|to| image.modified <= to)
+            && self.has_references.is_none_or(|wanted| has_references(image) == wanted)
+            && self.matches_annotation(image)
+    }
+
+    fn matches_annotation(&self, image: &ImageFile) -> bool {
+        let annotation = image.annotation.as_ref();
+        let rating = annotation.and_then(|a| a.rating);
+        let favorite = annotation.is_some_and(|a| a.favorite);
+
+        self.tags.iter().all(|tag| {
+            let tag = tag.trim().to_lowercase();
+            annotation.is_some_and(|a| a.tags.contains(&tag))
+        })
+            && self.min_rating.is_none_or(|min| rating.is_some_and(|r| r >= min))
+            && self.favorite.is_none_or(|wanted| favorite == wanted)
+    }
+
+    /// Compare by the sort key; images missing the key always sort last
+    fn compare(&self, a: &ImageFile, b: &ImageFile) -> Ordering {
+        let text_key = |image: &ImageFile| -> Option<String> {
+            let meta = image.metadata.as_ref()?;
+            match self.sort {
+                SortKey::MetadataTimestamp => meta.timestamp.clone(),
+                SortKey::Model => meta.model.clone(),
+                SortKey::Mtime | SortKey::AspectRatio | SortKey::Rating => None,
+            }
+        };
+
+        let ordering = match self.sort {
+            SortKey::Mtime => self.directed(a.modified.cmp(&b.modified)),
+            SortKey::Rating => {
+                let rating = |image: &ImageFile| image.annotation.as_ref().and_then(|a| a.rating);
+                self.missing_last(rating(a), rating(b), Ord::cmp)
+            }
+            SortKey::AspectRatio => {
+                let ratio = |image: &ImageFile| {
+                    image.metadata.as_ref()?.aspect_ratio.as_deref().and_then(parse_aspect_ratio)
+                };
+                self.missing_last(ratio(a), ratio(b), f64::total_cmp)
+            }
+            SortKey::MetadataTimestamp | SortKey::Model => {
+                self.missing_last(text_key(a), text_key(b), Ord::cmp)
+            }
+        };
+
+        // Fall back to path so pages are stable between calls
+        ordering.then_with(|| a.path.cmp(&b.path))
+    }
+
+    /// Compare two optional keys in the query's direction, with missing keys last either way
+    fn missing_last<T>(&self, a: Option<T>, b: Option<T>, compare: impl FnOnce(&T, &T) -> Ordering) -> Ordering {
+        match (a, b) {
+            (Some(x), Some(y)) => self.directed(compare(&x, &y)),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+
+    fn directed(&self, ordering: Ordering) -> Ordering {
+        match self.direction {
+            SortDirection::Asc => ordering,
+            SortDirection::Desc => ordering.reverse(),
+        }
+    }
+
+    /// Filter, sort and paginate a set of images
+    pub fn run(&self, images: Vec<ImageFile>) -> ImagePage {
+        let mut matching: Vec<ImageFile> = images
+            .into_iter()
+            .filter(|image| self.matches(image))
+            .collect();
+        matching.sort_by(|a, b| self.compare(a, b));
+
+        let total = matching.len();
+        let limit = self.limit.unwrap_or(total);
+        let images = matching
+            .into_iter()
+            .skip(self.offset)
+            .take(limit)
+            .collect();
+
+        ImagePage {
+            images,
+            total,
+            offset: self.offset,
+        }
+    }
+}
+
+/// Parse a "w:h" aspect ratio into width / height
+fn parse_aspect_ratio(value: &str) -> Option<f64> {
+    let (width, height) = value.split_once(':')?;
+    let width: f64 = width.trim().parse().ok()?;
+    let height: f64 = height.trim().parse().ok()?;
+    let ratio = width / height;
+    (width > 0.0 && ratio.is_finite()).then_some(ratio)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::ImageMetadata;
+
+    fn image(name: &str, modified: i64, model: Option<&str>, refs: usize) -> ImageFile {
+        ImageFile {
+            path: format!("/images/{}", name),
+            filename: name.to_string(),
+            folder: String::new(),
+            timestamp: String::new(),
+            modified,
+            mime_type: "image/png".to_string(),
+            metadata: model.map(|m| ImageMetadata {
+                model: Some(m.to_string()),
+                reference_images: vec!["ref.png".to_string(); refs],
+                ..Default::default()
+            }),
+            properties: None,
+            annotation: None,
+        }
+    }
+
+    #[test]
+    fn test_query_filters_sorts_and_pages() {
+        let images = vec![
+            image("a.png", 1, Some("flash"), 0),
+            image("b.png", 3, Some("pro"), 2),
+            image("c.png", 2, Some("flash"), 1),
+            image("d.png", 4, None, 0),
+        ];
+
+        let query = ImageQuery {
+            model: Some("flash".to_string()),
+            ..Default::default()
+        };
+        let page = query.run(images.clone());
+        assert_eq!(page.total, 2);
+        assert_eq!(page.images[0].filename, "c.png");
+
+        let query = ImageQuery {
+            sort: SortKey::Model,
+            direction: SortDirection::Asc,
+            offset: 1,
+            limit: Some(2),
+            ..Default::default()
+        };
+        let page = query.run(images.clone());
+        assert_eq!(page.total, 4);
+        let names: Vec<&str> = page.images.iter().map(|i| i.filename.as_str()).collect();
+        assert_eq!(names, vec!["c.png", "b.png"]);
+
+        let query = ImageQuery {
+            has_references: Some(true),
+            from: Some(3),
+            ..Default::default()
+        };
+        let page = query.run(images);
+        assert_eq!(page.total, 1);
+        assert_eq!(page.images[0].filename, "b.png");
+    }
+
+    #[test]
+    fn test_aspect_ratio_sorts_numerically() {
+        let with_ratio = |name: &str, ratio: &str| {
+            let mut image = image(name, 0, Some("flash"), 0);
+            image.metadata.as_mut().unwrap().aspect_ratio = Some(ratio.to_string());
+            image
+        };
+        let images = vec![
+            with_ratio("wide.png", "16:9"),
+            with_ratio("square.png", "1:1"),
+            with_ratio("broken.png", "wide"),
+            with_ratio("photo.png", "4:3"),
+            with_ratio("tall.png", "9:16"),
+        ];
+
+        let query = ImageQuery {
+            sort: SortKey::AspectRatio,
+            direction: SortDirection::Asc,
+            ..Default::default()
+        };
+        let page = query.run(images);
+        let names: Vec<&str> = page.images.iter().map(|i| i.filename.as_str()).collect();
+        assert_eq!(names, vec!["tall.png", "square.png", "photo.png", "wide.png", "broken.png"]);
+    }
+}
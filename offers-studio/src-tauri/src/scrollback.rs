@@ -0,0 +1,115 @@
+//! Bounded history of a PTY's output
+//! Lets a terminal that was reloaded or remounted reattach to a running PTY
+//! and redraw what was on screen instead of starting blank
+
+use std::collections::VecDeque;
+use serde::Serialize;
+
+/// Output kept per PTY before the oldest is dropped
+pub const SCROLLBACK_LIMIT: usize = 2 * 1024 * 1024;
+
+/// Output retained for a reattaching terminal
+#[derive(Debug, Serialize)]
+pub struct ScrollbackSnapshot {
+    pub data: String,
+    /// Byte offset of the end of `data` in the PTY's whole output; pass it back as `since`
+    pub offset: u64,
+    /// True if output older than `data` has been dropped
+    pub truncated: bool,
+}
+
+/// Ring buffer of output chunks. Whole chunks are dropped so the data stays
+/// valid UTF-8; the reader only ever pushes complete characters.
+pub struct Scrollback {
+    chunks: VecDeque<String>,
+    /// Bytes currently held
+    len: usize,
+    /// Bytes ever pushed, so callers can ask for only what they haven't seen
+    total: u64,
+    limit: usize,
+}
+
+impl Scrollback {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            chunks: VecDeque::new(),
+            len: 0,
+            total: 0,
+            limit,
+        }
+    }
+
+    /// Append output, returning the offset of its end in the PTY's whole output
+    pub fn push(&mut self, data: &str) -> u64 {
+        if data.is_empty() {
+            return self.total;
+        }
+        self.chunks.push_back(data.to_string());
+        self.len += data.len();
+        self.total += data.len() as u64;
+
+        // Always keep the newest chunk, even if it alone is over the limit
+        while self.len > self.limit && self.chunks.len() > 1 {
+            if let Some(dropped) = self.chunks.pop_front() {
+                self.len -= dropped.len();
+            }
+        }
+        self.total
+    }
+
+    /// Offset of the oldest byte still held
+    fn start(&self) -> u64 {
+        self.total - self.len as u64
+    }
+
+    /// Everything held, or only what came after `since`
+    pub fn snapshot(&self, since: Option<u64>) -> ScrollbackSnapshot {
+        let since = since.unwrap_or(0).min(self.total);
+        let truncated = since < self.start();
+
+        let mut data = String::new();
+        let mut position = self.start();
+        for chunk in &self.chunks {
+            let end = position + chunk.len() as u64;
+            if end > since {
+                // Cut part-way into a chunk only on a character boundary
+                let skip = since.saturating_sub(position) as usize;
+                let skip = (skip..=chunk.len()).find(|&i| chunk.is_char_boundary(i)).unwrap_or(chunk.len());
+                data.push_str(&chunk[skip..]);
+            }
+            position = end;
+        }
+
+        ScrollbackSnapshot {
+            data,
+            offset: self.total,
+            truncated,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrollback_drops_oldest_and_resumes() {
+        let mut scrollback = Scrollback::new(12);
+        assert_eq!(scrollback.push("hello "), 6);
+        assert_eq!(scrollback.push("wörld"), 12);
+        assert_eq!(scrollback.snapshot(None).data, "hello wörld");
+
+        // Over the limit: the oldest chunk goes
+        scrollback.push("!!");
+        let all = scrollback.snapshot(None);
+        assert_eq!(all.data, "wörld!!");
+        assert_eq!(all.offset, 14);
+        assert!(all.truncated);
+
+        // Only what came after an earlier snapshot
+        let since = scrollback.snapshot(Some(12));
+        assert_eq!(since.data, "!!");
+        assert!(!since.truncated);
+        assert_eq!(scrollback.snapshot(Some(14)).data, "");
+    }
+}
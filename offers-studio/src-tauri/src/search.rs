@@ -0,0 +1,238 @@
+//! Full-text search over image prompts and descriptions
+//! An in-memory inverted index kept alongside each project's catalog
+
+use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+
+/// Characters of context kept on either side of the first match in a snippet
+const SNIPPET_CONTEXT: usize = 60;
+
+/// Bonus applied when the whole query appears verbatim, so exact phrases rank first
+const PHRASE_BONUS: f64 = 2.0;
+
+/// Split text into (start, end) byte ranges of alphanumeric words
+fn word_ranges(text: &str) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+
+    for (i, c) in text.char_indices() {
+        if c.is_alphanumeric() {
+            if start.is_none() {
+                start = Some(i);
+            }
+        } else if let Some(s) = start.take() {
+            ranges.push((s, i));
+        }
+    }
+    if let Some(s) = start {
+        ranges.push((s, text.len()));
+    }
+
+    ranges
+}
+
+/// Lowercased words of a piece of text
+pub fn tokenize(text: &str) -> Vec<String> {
+    word_ranges(text)
+        .into_iter()
+        .map(|(s, e)| text[s..e].to_lowercase())
+        .collect()
+}
+
+/// A run of snippet text, marked if it matched the query
+#[derive(Debug, Clone, Serialize)]
+pub struct SnippetPart {
+    pub text: String,
+    pub highlight: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Snippet {
+    /// Which metadata field the snippet came from ("prompt" or "description")
+    pub field: String,
+    pub parts: Vec<SnippetPart>,
+}
+
+/// Build a highlighted excerpt around the first query term found in `text`
+pub fn snippet(field: &str, text: &str, terms: &HashSet<String>) -> Option<Snippet> {
+    let words = word_ranges(text);
+    let matched: Vec<(usize, usize)> = words
+        .into_iter()
+        .filter(|&(s, e)| terms.contains(&text[s..e].to_lowercase()))
+        .collect();
+    let &(first_start, _) = matched.first()?;
+
+    // Widen the window to char boundaries around the first hit
+    let mut start = first_start.saturating_sub(SNIPPET_CONTEXT);
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = (first_start + SNIPPET_CONTEXT * 2).min(text.len());
+    while !text.is_char_boundary(end) {
+        end += 1;
+    }
+
+    let mut parts = Vec::new();
+    if start > 0 {
+        parts.push(SnippetPart { text: "…".to_string(), highlight: false });
+    }
+
+    let mut cursor = start;
+    for (s, e) in matched {
+        if s < start || e > end {
+            continue;
+        }
+        if s > cursor {
+            parts.push(SnippetPart { text: text[cursor..s].to_string(), highlight: false });
+        }
+        parts.push(SnippetPart { text: text[s..e].to_string(), highlight: true });
+        cursor = e;
+    }
+    if cursor < end {
+        parts.push(SnippetPart { text: text[cursor..end].to_string(), highlight: false });
+    }
+    if end < text.len() {
+        parts.push(SnippetPart { text: "…".to_string(), highlight: false });
+    }
+
+    Some(Snippet {
+        field: field.to_string(),
+        parts,
+    })
+}
+
+/// Inverted index from token to the documents (catalog keys) containing it
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    /// token -> (document key -> term frequency)
+    postings: HashMap<String, HashMap<String, u32>>,
+    /// document key -> its distinct tokens, so documents can be removed cleanly
+    documents: HashMap<String, Vec<String>>,
+    /// document key -> lowercased full text, for phrase matching
+    texts: HashMap<String, String>,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index (or re-index) a document
+    pub fn insert(&mut self, key: &str, text: &str) {
+        self.remove(key);
+
+        let mut frequencies: HashMap<String, u32> = HashMap::new();
+        for token in tokenize(text) {
+            *frequencies.entry(token).or_insert(0) += 1;
+        }
+        if frequencies.is_empty() {
+            return;
+        }
+
+        let tokens: Vec<String> = frequencies.keys().cloned().collect();
+        for (token, count) in frequencies {
+            self.postings
+                .entry(token)
+                .or_default()
+                .insert(key.to_string(), count);
+        }
+        self.documents.insert(key.to_string(), tokens);
+        self.texts.insert(key.to_string(), text.to_lowercase());
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        if let Some(tokens) = self.documents.remove(key) {
+            for token in tokens {
+                if let Some(docs) = self.postings.get_mut(&token) {
+                    docs.remove(key);
+                    if docs.is_empty() {
+                        self.postings.remove(&token);
+                    }
+                }
+            }
+        }
+        self.texts.remove(key);
+    }
+
+    /// Find documents containing every query term, best matches first.
+    /// Scores are tf-idf with a bonus for the exact phrase.
+    pub fn search(&self, query: &str) -> Vec<(String, f64)> {
+        let terms: Vec<String> = tokenize(query)
+            .into_iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let total_docs = self.documents.len() as f64;
+        let mut scores: HashMap<&str, f64> = HashMap::new();
+
+        for (i, term) in terms.iter().enumerate() {
+            let docs = match self.postings.get(term) {
+                Some(d) => d,
+                None => return Vec::new(),
+            };
+            let idf = (1.0 + total_docs / docs.len() as f64).ln();
+
+            if i == 0 {
+                for (key, tf) in docs {
+                    scores.insert(key.as_str(), *tf as f64 * idf);
+                }
+            } else {
+                // Keep only documents that also contain this term
+                scores.retain(|key, _| docs.contains_key(*key));
+                for (key, score) in scores.iter_mut() {
+                    *score += docs[*key] as f64 * idf;
+                }
+            }
+        }
+
+        let phrase = query.trim().to_lowercase();
+        let mut results: Vec<(String, f64)> = scores
+            .into_iter()
+            .map(|(key, score)| {
+                let is_phrase = terms.len() > 1
+                    && self.texts.get(key).is_some_and(|text| text.contains(&phrase));
+                let score = if is_phrase { score * PHRASE_BONUS } else { score };
+                (key.to_string(), score)
+            })
+            .collect();
+
+        results.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_ranks_phrase_matches_first() {
+        let mut index = SearchIndex::new();
+        index.insert("a.png", "A golden door in a stone wall");
+        index.insert("b.png", "Golden hour light on a wooden door");
+        index.insert("c.png", "A silver door");
+
+        let results = index.search("golden door");
+        let keys: Vec<&str> = results.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["a.png", "b.png"]);
+
+        index.remove("a.png");
+        assert_eq!(index.search("golden door").len(), 1);
+    }
+
+    #[test]
+    fn test_snippet_highlights_terms() {
+        let terms: HashSet<String> = ["door".to_string()].into_iter().collect();
+        let snippet = snippet("prompt", "A golden Door", &terms).unwrap();
+        let highlighted: Vec<&str> = snippet.parts
+            .iter()
+            .filter(|p| p.highlight)
+            .map(|p| p.text.as_str())
+            .collect();
+        assert_eq!(highlighted, vec!["Door"]);
+    }
+}
@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionInfo {
@@ -210,6 +211,31 @@ pub fn list_sessions(project_path: &str) -> Result<Vec<SessionInfo>, String> {
     Ok(sessions)
 }
 
+/// Sessions written to since the given time, used to work out which session a
+/// terminal started with a plain `claude` ended up in
+pub fn sessions_since(project_path: &str, since: SystemTime) -> Vec<String> {
+    let project_dir = match find_project_dir(project_path) {
+        Ok(dir) => dir,
+        Err(_) => return Vec::new(),
+    };
+    let entries = match fs::read_dir(&project_dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("jsonl"))
+        .filter(|path| {
+            fs::metadata(path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified >= since)
+        })
+        .filter_map(|path| path.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .collect()
+}
+
 /// Get custom session names from persistent storage
 fn get_session_names_file() -> Result<PathBuf, String> {
     let home = dirs::home_dir().ok_or("Could not determine home directory")?;
@@ -0,0 +1,194 @@
+//! Debouncing for gallery watcher events
+//! Holds image events back until the file has finished being written, merging
+//! the bursts of create/modify/metadata events a single generation produces
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How long a file must go without events or size changes before it's considered written
+pub const SETTLE_WINDOW: Duration = Duration::from_millis(400);
+
+/// How long a new image waits for its companion JSON before being emitted without it
+pub const METADATA_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// A change to the gallery, with companion JSON changes attributed to their image
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageEvent {
+    Added(PathBuf),
+    Modified(PathBuf),
+    Removed(PathBuf),
+    Renamed(PathBuf, PathBuf),
+    /// The image's companion JSON was created, changed or removed
+    MetadataUpdated(PathBuf),
+    /// A folder was created, moved or deleted, so its contents need rescanning
+    FolderChanged(PathBuf),
+}
+
+impl ImageEvent {
+    /// The path the event is tracked under while it settles
+    fn key(&self) -> &Path {
+        match self {
+            ImageEvent::Added(p)
+            | ImageEvent::Modified(p)
+            | ImageEvent::Removed(p)
+            | ImageEvent::MetadataUpdated(p)
+            | ImageEvent::FolderChanged(p) => p,
+            ImageEvent::Renamed(_, to) => to,
+        }
+    }
+}
+
+/// Combine a pending event with a newer one for the same path.
+/// Returns None when the two cancel out (created then deleted before settling).
+fn merge(existing: ImageEvent, new: ImageEvent) -> Option<ImageEvent> {
+    use ImageEvent::*;
+
+    match (existing, new) {
+        (Added(p), Modified(_) | MetadataUpdated(_)) => Some(Added(p)),
+        (Added(_), Removed(_)) => None,
+        (Modified(p), MetadataUpdated(_)) | (MetadataUpdated(p), Modified(_)) => Some(Modified(p)),
+        // Deleted and recreated, e.g. an overwrite
+        (Removed(p), Added(_)) => Some(Modified(p)),
+        (Renamed(from, to), Modified(_) | MetadataUpdated(_)) => Some(Renamed(from, to)),
+        (Renamed(from, _), Removed(_)) => Some(Removed(from)),
+        (_, new) => Some(new),
+    }
+}
+
+struct Pending {
+    event: ImageEvent,
+    first_seen: Instant,
+    last_event: Instant,
+    last_size: Option<u64>,
+    stable_since: Instant,
+}
+
+/// Queue of events waiting for their files to settle
+#[derive(Default)]
+pub struct Settler {
+    pending: HashMap<PathBuf, Pending>,
+}
+
+impl Settler {
+    pub fn push(&mut self, event: ImageEvent, now: Instant) {
+        // A rename moves whatever was pending under the old path
+        let event = match event {
+            ImageEvent::Renamed(from, to) => match self.pending.remove(&from).map(|p| p.event) {
+                Some(ImageEvent::Added(_)) => ImageEvent::Added(to),
+                Some(ImageEvent::Renamed(original, _)) => ImageEvent::Renamed(original, to),
+                _ => ImageEvent::Renamed(from, to),
+            },
+            other => other,
+        };
+
+        let key = event.key().to_path_buf();
+        match self.pending.remove(&key) {
+            Some(mut pending) => {
+                if let Some(merged) = merge(pending.event, event) {
+                    pending.event = merged;
+                    pending.last_event = now;
+                    self.pending.insert(key, pending);
+                }
+            }
+            None => {
+                self.pending.insert(key, Pending {
+                    event,
+                    first_seen: now,
+                    last_event: now,
+                    last_size: None,
+                    stable_since: now,
+                });
+            }
+        }
+    }
+
+    /// Take every event whose file has settled, oldest first
+    pub fn take_ready(&mut self, now: Instant) -> Vec<ImageEvent> {
+        let mut ready = Vec::new();
+        let mut vanished = Vec::new();
+
+        for (key, pending) in self.pending.iter_mut() {
+            if now.duration_since(pending.last_event) < SETTLE_WINDOW {
+                continue;
+            }
+
+            let needs_content = matches!(pending.event, ImageEvent::Added(_) | ImageEvent::Modified(_));
+            if needs_content {
+                let size = match fs::metadata(key) {
+                    Ok(m) => m.len(),
+                    Err(_) => {
+                        // Gone before it settled; a removal event will follow if it mattered
+                        vanished.push(key.clone());
+                        continue;
+                    }
+                };
+
+                if pending.last_size != Some(size) {
+                    pending.last_size = Some(size);
+                    pending.stable_since = now;
+                    continue;
+                }
+                if now.duration_since(pending.stable_since) < SETTLE_WINDOW {
+                    continue;
+                }
+            }
+
+            if let ImageEvent::Added(path) = &pending.event {
+                let waiting_for_json = !path.with_extension("json").exists()
+                    && now.duration_since(pending.first_seen) < METADATA_TIMEOUT;
+                if waiting_for_json {
+                    continue;
+                }
+            }
+
+            ready.push((pending.first_seen, key.clone()));
+        }
+
+        for key in vanished {
+            self.pending.remove(&key);
+        }
+
+        ready.sort();
+        ready
+            .into_iter()
+            .filter_map(|(_, key)| self.pending.remove(&key).map(|p| p.event))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settler_waits_for_size_and_metadata() {
+        let dir = std::env::temp_dir().join(format!("settle-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let image = dir.join("generated_1.png");
+        fs::write(&image, b"partial").unwrap();
+
+        let start = Instant::now();
+        let mut settler = Settler::default();
+        settler.push(ImageEvent::Added(image.clone()), start);
+        settler.push(ImageEvent::Modified(image.clone()), start);
+
+        // First check after the window records the size; it must then hold steady
+        let t1 = start + SETTLE_WINDOW;
+        assert!(settler.take_ready(t1).is_empty());
+        let t2 = t1 + SETTLE_WINDOW;
+        assert!(settler.take_ready(t2).is_empty(), "still waiting for the JSON");
+
+        fs::write(image.with_extension("json"), "{}").unwrap();
+        assert_eq!(settler.take_ready(t2), vec![ImageEvent::Added(image.clone())]);
+        assert!(settler.pending.is_empty());
+
+        // Created then deleted before settling produces nothing
+        settler.push(ImageEvent::Added(dir.join("tmp.png")), t2);
+        settler.push(ImageEvent::Removed(dir.join("tmp.png")), t2);
+        assert!(settler.pending.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
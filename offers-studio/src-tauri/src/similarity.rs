@@ -0,0 +1,209 @@
+//! Duplicate and near-duplicate detection
+//! Exact duplicates share a content hash; near-duplicates (variations from the
+//! same prompt, re-encodes, resizes) have perceptual hashes a few bits apart
+
+use std::collections::HashMap;
+use std::path::Path;
+use image::imageops::FilterType;
+use serde::Serialize;
+use crate::catalog::{Fingerprint, ImageFile};
+
+/// Default Hamming distance (out of 64 bits) under which two images count as similar
+pub const DEFAULT_THRESHOLD: u32 = 10;
+
+/// Difference hash: shrink to 9x8 grayscale and record whether each pixel
+/// is brighter than its right-hand neighbour
+pub fn perceptual_hash(path: &Path) -> Result<u64, String> {
+    let image = image::ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|e| format!("Failed to open image: {}", e))?
+        .decode()
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+
+    let small = image.grayscale().resize_exact(9, 8, FilterType::Triangle).to_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | (left > right) as u64;
+        }
+    }
+    Ok(hash)
+}
+
+fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimilarImage {
+    pub image: ImageFile,
+    /// Bits that differ between the perceptual hashes (0 is visually identical)
+    pub distance: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    /// Content hash for exact duplicates, or the first image's perceptual hash as hex
+    pub hash: String,
+    /// Oldest first, so the original is the one to keep
+    pub images: Vec<ImageFile>,
+}
+
+/// Images within `threshold` bits of the target, closest first
+pub fn find_similar(fingerprints: &[Fingerprint], target: &Path, threshold: u32) -> Result<Vec<SimilarImage>, String> {
+    let target_path = target.to_string_lossy();
+    let target_hash = fingerprints
+        .iter()
+        .find(|f| f.image.path == target_path)
+        .ok_or_else(|| format!("Image not found in catalog: {}", target.display()))?
+        .perceptual_hash
+        .ok_or_else(|| format!("Can't compare {}: its format can't be decoded", target.display()))?;
+
+    let mut similar: Vec<SimilarImage> = fingerprints
+        .iter()
+        .filter(|f| f.image.path != target_path)
+        .filter_map(|f| {
+            let d = distance(target_hash, f.perceptual_hash?);
+            (d <= threshold).then(|| SimilarImage { image: f.image.clone(), distance: d })
+        })
+        .collect();
+
+    similar.sort_by(|a, b| a.distance.cmp(&b.distance).then_with(|| a.image.path.cmp(&b.image.path)));
+    Ok(similar)
+}
+
+fn sorted_group(hash: String, mut images: Vec<ImageFile>) -> DuplicateGroup {
+    images.sort_by(|a, b| a.modified.cmp(&b.modified).then_with(|| a.path.cmp(&b.path)));
+    DuplicateGroup { hash, images }
+}
+
+/// Groups of byte-identical images
+pub fn exact_duplicates(fingerprints: &[Fingerprint]) -> Vec<DuplicateGroup> {
+    let mut by_hash: HashMap<&str, Vec<ImageFile>> = HashMap::new();
+    for f in fingerprints {
+        by_hash.entry(&f.content_hash).or_default().push(f.image.clone());
+    }
+
+    let mut groups: Vec<DuplicateGroup> = by_hash
+        .into_iter()
+        .filter(|(_, images)| images.len() > 1)
+        .map(|(hash, images)| sorted_group(hash.to_string(), images))
+        .collect();
+    groups.sort_by(|a, b| b.images.len().cmp(&a.images.len()).then_with(|| a.hash.cmp(&b.hash)));
+    groups
+}
+
+fn find_root(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    // Path compression
+    let mut node = i;
+    while parents[node] != root {
+        let next = parents[node];
+        parents[node] = root;
+        node = next;
+    }
+    root
+}
+
+/// Groups of images whose perceptual hashes are within `threshold` bits of
+/// another image in the group (so a chain of small edits lands in one group)
+pub fn near_duplicates(fingerprints: &[Fingerprint], threshold: u32) -> Vec<DuplicateGroup> {
+    let hashed: Vec<(&Fingerprint, u64)> = fingerprints
+        .iter()
+        .filter_map(|f| Some((f, f.perceptual_hash?)))
+        .collect();
+
+    let mut parents: Vec<usize> = (0..hashed.len()).collect();
+    for i in 0..hashed.len() {
+        for j in i + 1..hashed.len() {
+            if distance(hashed[i].1, hashed[j].1) <= threshold {
+                let (a, b) = (find_root(&mut parents, i), find_root(&mut parents, j));
+                parents[a] = b;
+            }
+        }
+    }
+
+    let mut by_root: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..hashed.len() {
+        by_root.entry(find_root(&mut parents, i)).or_default().push(i);
+    }
+
+    let mut groups: Vec<DuplicateGroup> = by_root
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|members| {
+            let hash = format!("{:016x}", hashed[members[0]].1);
+            sorted_group(hash, members.iter().map(|&i| hashed[i].0.image.clone()).collect())
+        })
+        .collect();
+    groups.sort_by(|a, b| b.images.len().cmp(&a.images.len()).then_with(|| a.hash.cmp(&b.hash)));
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn test_perceptual_hash_groups_variations() {
+        let dir = std::env::temp_dir().join(format!("similarity-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let gradient = |width: u32, height: u32, boost: u8| {
+            RgbImage::from_fn(width, height, |x, y| {
+                let v = ((x * 255 / width) as u8).saturating_add(boost);
+                Rgb([v, (y * 255 / height) as u8, 128])
+            })
+        };
+        let images = [
+            ("original.png", gradient(64, 64, 0)),
+            ("brighter.png", gradient(64, 64, 20)),
+            ("resized.png", gradient(128, 128, 0)),
+            ("different.png", RgbImage::from_fn(64, 64, |x, y| Rgb([if (x / 8 + y / 8) % 2 == 0 { 255 } else { 0 }; 3]))),
+        ];
+
+        let mut fingerprints = Vec::new();
+        for (name, pixels) in &images {
+            let path = dir.join(name);
+            pixels.save(&path).unwrap();
+            fingerprints.push(Fingerprint {
+                image: ImageFile {
+                    path: path.to_string_lossy().to_string(),
+                    filename: name.to_string(),
+                    folder: String::new(),
+                    timestamp: String::new(),
+                    modified: 0,
+                    mime_type: "image/png".to_string(),
+                    metadata: None,
+                    properties: None,
+                    annotation: None,
+                },
+                content_hash: name.to_string(),
+                perceptual_hash: Some(perceptual_hash(&path).unwrap()),
+            });
+        }
+
+        let similar = find_similar(&fingerprints, &dir.join("original.png"), DEFAULT_THRESHOLD).unwrap();
+        let names: Vec<&str> = similar.iter().map(|s| s.image.filename.as_str()).collect();
+        assert!(names.contains(&"brighter.png") && names.contains(&"resized.png"));
+        assert!(!names.contains(&"different.png"));
+
+        let groups = near_duplicates(&fingerprints, DEFAULT_THRESHOLD);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].images.len(), 3);
+
+        fingerprints[1].content_hash = "original.png".to_string();
+        let exact = exact_duplicates(&fingerprints);
+        assert_eq!(exact.len(), 1);
+        assert_eq!(exact[0].images.len(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+//! Downscaled gallery previews
+//! Thumbnails are cached in the app cache dir per project, keyed by the image's content
+//! hash, so a modified image gets a fresh thumbnail and renames don't regenerate anything
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use image::codecs::jpeg::JpegEncoder;
+use serde::Deserialize;
+use tauri::{AppHandle, Manager};
+use crate::catalog::CatalogState;
+use crate::formats;
+
+/// JPEG quality for cached thumbnails
+const THUMBNAIL_QUALITY: u8 = 80;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThumbnailSize {
+    /// Grid tiles
+    Small,
+    /// Latest-image preview
+    #[default]
+    Medium,
+    /// Modal before the full image has loaded
+    Large,
+}
+
+impl ThumbnailSize {
+    /// Longest edge in pixels
+    fn max_dimension(self) -> u32 {
+        match self {
+            ThumbnailSize::Small => 256,
+            ThumbnailSize::Medium => 512,
+            ThumbnailSize::Large => 1024,
+        }
+    }
+}
+
+/// Get the directory thumbnails are cached in
+pub fn get_cache_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_cache_dir()
+        .map(|dir| dir.join("thumbnails"))
+        .map_err(|e| format!("Failed to get cache directory: {}", e))
+}
+
+/// Get the directory a project's thumbnails are cached in
+fn get_project_cache_dir(app: &AppHandle, project_path: &str) -> Result<PathBuf, String> {
+    get_cache_dir(app).map(|dir| dir.join(format!("{:x}", md5::compute(project_path))))
+}
+
+/// Decode an image and write a downscaled JPEG of it
+fn render(source: &Path, destination: &Path, max_dimension: u32) -> Result<(), String> {
+    let image = image::ImageReader::open(source)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|e| format!("Failed to open image: {}", e))?
+        .decode()
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+
+    // JPEG has no alpha channel, and generated images are opaque anyway
+    let thumbnail = image.thumbnail(max_dimension, max_dimension).to_rgb8();
+
+    // Write to a temp file first so a half-written thumbnail is never served.
+    // The name is unique since two requests can render the same thumbnail at once;
+    // whichever renames last wins, and both results are identical.
+    let tmp_path = destination.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    let result = write_jpeg(&tmp_path, &thumbnail).and_then(|()| {
+        fs::rename(&tmp_path, destination)
+            .map_err(|e| format!("Failed to save thumbnail: {}", e))
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+fn write_jpeg(path: &Path, image: &image::RgbImage) -> Result<(), String> {
+    let file = fs::File::create(path)
+        .map_err(|e| format!("Failed to create thumbnail: {}", e))?;
+    let mut writer = std::io::BufWriter::new(file);
+    JpegEncoder::new_with_quality(&mut writer, THUMBNAIL_QUALITY)
+        .encode_image(image)
+        .map_err(|e| format!("Failed to encode thumbnail: {}", e))?;
+    writer
+        .into_inner()
+        .map_err(|e| format!("Failed to write thumbnail: {}", e))?;
+    Ok(())
+}
+
+/// Get the path of a cached thumbnail, generating it if needed
+pub fn get_or_create(
+    app: &AppHandle,
+    catalog: &CatalogState,
+    project_path: &str,
+    image_path: &Path,
+    size: ThumbnailSize,
+) -> Result<PathBuf, String> {
+    let format = formats::detect(image_path)
+        .ok_or_else(|| format!("Not a supported image: {}", image_path.display()))?;
+    if !format.is_decodable() {
+        return Err(format!("Thumbnails aren't supported for {}", format.mime_type()));
+    }
+
+    // Re-hashed if the file changed since it was indexed, so a stale thumbnail isn't served
+    let hash = catalog.content_hash(project_path, image_path)?;
+    let cache_dir = get_project_cache_dir(app, project_path)?;
+    let thumbnail_path = cache_dir.join(format!("{}-{}.jpg", hash, size.max_dimension()));
+
+    if !thumbnail_path.exists() {
+        fs::create_dir_all(&cache_dir)
+            .map_err(|e| format!("Failed to create thumbnail directory: {}", e))?;
+        render(image_path, &thumbnail_path, size.max_dimension())?;
+    }
+
+    Ok(thumbnail_path)
+}
+
+/// Total size of the files in a directory and its subdirectories
+fn dir_size(dir: &Path) -> u64 {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            Some(if meta.is_dir() { dir_size(&entry.path()) } else { meta.len() })
+        })
+        .sum()
+}
+
+/// Delete a project's thumbnails of images that are no longer in its catalog,
+/// returning how many bytes were freed
+pub fn prune(app: &AppHandle, project_path: &str, live_hashes: &HashSet<String>) -> Result<u64, String> {
+    let cache_dir = get_project_cache_dir(app, project_path)?;
+    if !cache_dir.exists() {
+        return Ok(0);
+    }
+
+    let mut freed = 0;
+    let entries = fs::read_dir(&cache_dir)
+        .map_err(|e| format!("Failed to read thumbnail directory: {}", e))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        // Named `{hash}-{size}.jpg`, or `.tmp` while being written
+        let hash = name.split_once('-').map_or(name.as_str(), |(hash, _)| hash);
+        if live_hashes.contains(hash) {
+            continue;
+        }
+        let size = entry.metadata().map(|m| m.len()).unwrap_or_default();
+        if fs::remove_file(entry.path()).is_ok() {
+            freed += size;
+        }
+    }
+    Ok(freed)
+}
+
+/// Delete every cached thumbnail, returning how many bytes were freed
+pub fn clear_cache(app: &AppHandle) -> Result<u64, String> {
+    let cache_dir = get_cache_dir(app)?;
+    if !cache_dir.exists() {
+        return Ok(0);
+    }
+
+    let freed = dir_size(&cache_dir);
+
+    fs::remove_dir_all(&cache_dir)
+        .map_err(|e| format!("Failed to clear thumbnail cache: {}", e))?;
+
+    Ok(freed)
+}
@@ -0,0 +1,262 @@
+//! Project-local trash for generated images
+//! Deleting moves an image and its companion JSON into .genimage-studio/trash,
+//! so they can be restored together until the trash is emptied
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::catalog::{get_images_dir, get_studio_dir};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashItem {
+    pub id: String,
+    /// Where the image lived, relative to generated_images with '/' separators
+    pub original_path: String,
+    /// RFC 3339 time it was deleted
+    pub deleted_at: String,
+    /// Whether the companion JSON was trashed along with the image
+    pub has_metadata: bool,
+    /// Combined size of the image and its JSON in bytes
+    pub size: u64,
+}
+
+/// Get the trash directory for a project
+fn get_trash_dir(project_path: &str) -> PathBuf {
+    get_studio_dir(project_path).join("trash")
+}
+
+fn index_path(project_path: &str) -> PathBuf {
+    get_trash_dir(project_path).join("index.json")
+}
+
+fn load_index(project_path: &str) -> Vec<TrashItem> {
+    fs::read_to_string(index_path(project_path))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_index(project_path: &str, items: &[TrashItem]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(items)
+        .map_err(|e| format!("Failed to serialize trash index: {}", e))?;
+    let path = index_path(project_path);
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, content)
+        .map_err(|e| format!("Failed to write trash index: {}", e))?;
+    fs::rename(&tmp_path, &path)
+        .map_err(|e| format!("Failed to replace trash index: {}", e))
+}
+
+/// Directory holding a trashed item's files
+fn item_dir(project_path: &str, id: &str) -> PathBuf {
+    get_trash_dir(project_path).join(id)
+}
+
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or_default()
+}
+
+/// Path of an image relative to generated_images, refusing anything outside it
+/// (or in a hidden folder) so the command can't be used to move arbitrary files
+fn relative_image_path(project_path: &str, path: &Path) -> Result<String, String> {
+    let relative = path
+        .strip_prefix(get_images_dir(project_path))
+        .map_err(|_| format!("Not in generated_images: {}", path.display()))?;
+
+    let mut parts = Vec::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) if !part.to_string_lossy().starts_with('.') => {
+                parts.push(part.to_string_lossy().to_string());
+            }
+            _ => return Err(format!("Not in generated_images: {}", path.display())),
+        }
+    }
+    if parts.is_empty() {
+        return Err(format!("Not an image: {}", path.display()));
+    }
+    Ok(parts.join("/"))
+}
+
+/// Move an image and its companion JSON into the trash
+pub fn delete(project_path: &str, path: &Path) -> Result<TrashItem, String> {
+    let original_path = relative_image_path(project_path, path)?;
+    if !path.is_file() {
+        return Err(format!("Image not found: {}", path.display()));
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let dir = item_dir(project_path, &id);
+    fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create trash directory: {}", e))?;
+
+    let filename = path.file_name().ok_or_else(|| format!("Not an image: {}", path.display()))?;
+    let trashed_image = dir.join(filename);
+    let json_path = path.with_extension("json");
+    let size = file_size(path) + file_size(&json_path);
+
+    fs::rename(path, &trashed_image)
+        .map_err(|e| format!("Failed to move image to trash: {}", e))?;
+
+    let has_metadata = json_path.exists();
+    if has_metadata {
+        if let Err(e) = fs::rename(&json_path, trashed_image.with_extension("json")) {
+            // Put the image back rather than split the pair
+            let _ = fs::rename(&trashed_image, path);
+            return Err(format!("Failed to move metadata to trash: {}", e));
+        }
+    }
+
+    let item = TrashItem {
+        id,
+        original_path,
+        deleted_at: chrono::Local::now().to_rfc3339(),
+        has_metadata,
+        size,
+    };
+
+    let mut items = load_index(project_path);
+    items.push(item.clone());
+    save_index(project_path, &items)?;
+
+    Ok(item)
+}
+
+/// Everything in a project's trash, most recently deleted first
+pub fn list(project_path: &str) -> Vec<TrashItem> {
+    let mut items = load_index(project_path);
+    items.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+    items
+}
+
+/// A free path to restore to: the original, or `name-1.png`, `name-2.png`...
+/// if something new has taken its place (checking the JSON name too)
+fn restore_target(original: &Path) -> PathBuf {
+    let taken = |p: &Path| p.exists() || p.with_extension("json").exists();
+    if !taken(original) {
+        return original.to_path_buf();
+    }
+
+    let stem = original.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = original.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+    (1..)
+        .map(|n| original.with_file_name(format!("{}-{}.{}", stem, n, extension)))
+        .find(|candidate| !taken(candidate))
+        .unwrap_or_else(|| original.to_path_buf())
+}
+
+/// Move a trashed image (and its JSON) back into generated_images.
+/// Returns the path it was restored to.
+pub fn restore(project_path: &str, id: &str) -> Result<PathBuf, String> {
+    let mut items = load_index(project_path);
+    let index = items
+        .iter()
+        .position(|item| item.id == id)
+        .ok_or_else(|| format!("Not in trash: {}", id))?;
+    let item = &items[index];
+
+    let original = get_images_dir(project_path).join(&item.original_path);
+    let filename = original.file_name().ok_or_else(|| format!("Invalid trash entry: {}", id))?;
+    let trashed_image = item_dir(project_path, id).join(filename);
+    let target = restore_target(&original);
+
+    if let Some(folder) = target.parent() {
+        fs::create_dir_all(folder)
+            .map_err(|e| format!("Failed to recreate folder: {}", e))?;
+    }
+
+    // Restore the JSON first so the watcher finds it as soon as the image appears
+    if item.has_metadata {
+        fs::rename(trashed_image.with_extension("json"), target.with_extension("json"))
+            .map_err(|e| format!("Failed to restore metadata: {}", e))?;
+    }
+    fs::rename(&trashed_image, &target)
+        .map_err(|e| format!("Failed to restore image: {}", e))?;
+
+    let _ = fs::remove_dir_all(item_dir(project_path, id));
+    items.remove(index);
+    save_index(project_path, &items)?;
+
+    Ok(target)
+}
+
+/// Permanently delete items from the trash, or all of it if `ids` is None.
+/// Returns how many bytes were freed.
+pub fn empty(project_path: &str, ids: Option<&[String]>) -> Result<u64, String> {
+    let items = load_index(project_path);
+    let (mut purge, keep): (Vec<TrashItem>, Vec<TrashItem>) = items
+        .into_iter()
+        .partition(|item| ids.is_none_or(|ids| ids.contains(&item.id)));
+
+    let mut freed = 0;
+    let mut purged = 0;
+    let mut error = None;
+    for item in &purge {
+        match fs::remove_dir_all(item_dir(project_path, &item.id)) {
+            Ok(()) => freed += item.size,
+            // Already gone, e.g. removed by hand
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => {
+                error = Some(format!("Failed to empty trash: {}", e));
+                break;
+            }
+        }
+        purged += 1;
+    }
+
+    // Only forget what was actually purged, even if a later item failed
+    if purged > 0 {
+        let mut remaining = purge.split_off(purged);
+        remaining.extend(keep);
+        save_index(project_path, &remaining)?;
+    }
+    match error {
+        Some(e) => Err(e),
+        None => Ok(freed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delete_and_restore_keeps_metadata_paired() {
+        let project = std::env::temp_dir().join(format!("trash-test-{}", uuid::Uuid::new_v4()));
+        let project_path = project.to_string_lossy().to_string();
+        let folder = get_images_dir(&project_path).join("client");
+        fs::create_dir_all(&folder).unwrap();
+        let image = folder.join("generated_1.png");
+        fs::write(&image, b"png").unwrap();
+        fs::write(image.with_extension("json"), "{}").unwrap();
+
+        assert!(delete(&project_path, &project.join("notes.txt")).is_err());
+
+        let item = delete(&project_path, &image).unwrap();
+        assert_eq!(item.original_path, "client/generated_1.png");
+        assert!(item.has_metadata);
+        assert!(!image.exists() && !image.with_extension("json").exists());
+        assert_eq!(list(&project_path).len(), 1);
+
+        // A new image took the name in the meantime
+        fs::write(&image, b"new").unwrap();
+        let restored = restore(&project_path, &item.id).unwrap();
+        assert_eq!(restored, folder.join("generated_1-1.png"));
+        assert!(restored.with_extension("json").exists());
+        assert!(list(&project_path).is_empty());
+
+        let second = delete(&project_path, &image).unwrap();
+        assert_eq!(empty(&project_path, None).unwrap(), second.size);
+        assert!(!item_dir(&project_path, &second.id).exists());
+
+        // An item whose files are already gone is still dropped from the index
+        fs::write(&image, b"again").unwrap();
+        let third = delete(&project_path, &image).unwrap();
+        fs::remove_dir_all(item_dir(&project_path, &third.id)).unwrap();
+        assert_eq!(empty(&project_path, None).unwrap(), 0);
+        assert!(list(&project_path).is_empty());
+
+        fs::remove_dir_all(&project).unwrap();
+    }
+}
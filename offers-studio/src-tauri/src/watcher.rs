@@ -1,50 +1,500 @@
 use notify::{Watcher, RecursiveMode, Result, Event, EventKind};
-use std::path::Path;
+use notify::event::{ModifyKind, RenameMode};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 use parking_lot::Mutex;
-use tauri::{AppHandle, Emitter};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use crate::catalog::{self, CatalogState, ImageFile};
+use crate::formats;
+use crate::settle::{ImageEvent, Settler, SETTLE_WINDOW};
 
+/// How often the emitter thread checks for settled events
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a change the app made itself waits for the watcher to report it;
+/// longer than an event can spend settling, including the wait for its JSON
+const OWN_CHANGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A file system change after rename halves have been paired up
+#[derive(Debug)]
+enum Change {
+    Created(PathBuf),
+    Modified(PathBuf),
+    Removed(PathBuf),
+    Renamed(PathBuf, PathBuf),
+}
+
+#[derive(Clone, Serialize)]
+struct ImageRemovedPayload {
+    path: String,
+}
+
+#[derive(Clone, Serialize)]
+struct FolderChangedPayload {
+    path: String,
+}
+
+#[derive(Clone, Serialize)]
+struct ImageRenamedPayload {
+    from: String,
+    to: String,
+    image: Option<ImageFile>,
+}
+
+/// One entry of an `image-batch` event
+#[derive(Clone, Serialize)]
+struct BatchedEvent {
+    event: &'static str,
+    payload: serde_json::Value,
+}
+
+#[derive(Clone, Serialize)]
+struct BatchPayload {
+    project_path: String,
+    events: Vec<BatchedEvent>,
+}
+
+/// Turns raw notify events into changes.
+///
+/// Platforms report renames differently: inotify sends From, To and then Both,
+/// Windows sends From then To, and FSEvents sends an unordered Any per path.
+/// We pair a From (or a vanished Any) with the To that follows it and ignore Both.
+/// A From with no partner (moved out of the folder) is reported as a removal
+/// once the next event arrives or the settle window passes.
+#[derive(Default)]
+struct ChangeClassifier {
+    pending_from: Option<(PathBuf, Instant)>,
+}
+
+impl ChangeClassifier {
+    fn classify(&mut self, event: Event) -> Vec<Change> {
+        let mut changes = Vec::new();
+
+        match event.kind {
+            EventKind::Modify(ModifyKind::Name(mode)) => {
+                for path in event.paths {
+                    let is_source = match mode {
+                        RenameMode::From => true,
+                        RenameMode::To => false,
+                        // Both repeats a From/To pair we've already handled
+                        RenameMode::Both => return changes,
+                        _ => !path.exists(),
+                    };
+
+                    if is_source {
+                        changes.extend(self.flush());
+                        self.pending_from = Some((path, Instant::now()));
+                    } else {
+                        match self.pending_from.take() {
+                            Some((from, _)) => changes.push(Change::Renamed(from, path)),
+                            None => changes.push(Change::Created(path)),
+                        }
+                    }
+                }
+            }
+            EventKind::Create(_) => {
+                changes.extend(self.flush());
+                changes.extend(event.paths.into_iter().map(Change::Created));
+            }
+            // Permission and timestamp changes don't affect the gallery
+            EventKind::Modify(ModifyKind::Metadata(_)) => {}
+            EventKind::Modify(_) => {
+                changes.extend(self.flush());
+                changes.extend(event.paths.into_iter().map(Change::Modified));
+            }
+            EventKind::Remove(_) => {
+                changes.extend(self.flush());
+                changes.extend(event.paths.into_iter().map(Change::Removed));
+            }
+            _ => {}
+        }
+
+        changes
+    }
+
+    /// Report an unpaired rename source as removed
+    fn flush(&mut self) -> Option<Change> {
+        self.pending_from.take().map(|(path, _)| Change::Removed(path))
+    }
+
+    /// Give up waiting for the other half of a rename
+    fn flush_stale(&mut self, now: Instant) -> Option<Change> {
+        let stale = self.pending_from
+            .as_ref()
+            .is_some_and(|(_, since)| now.duration_since(*since) >= SETTLE_WINDOW);
+        if stale {
+            self.flush()
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether a path is (or, if it's gone, probably was) a folder
+fn is_folder_path(path: &Path) -> bool {
+    path.is_dir() || (!path.exists() && path.extension().is_none())
+}
+
+/// Whether a path is inside a hidden folder under generated_images, like our trash
+fn is_hidden(images_dir: &Path, path: &Path) -> bool {
+    path.strip_prefix(images_dir)
+        .map(|relative| {
+            relative.components().any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+        })
+        .unwrap_or(false)
+}
+
+/// Map a path-level change onto gallery events. `is_cataloged` is checked as the
+/// change arrives, to tell a file replacing an indexed image from a new one.
+fn to_image_events(change: Change, is_cataloged: &dyn Fn(&Path) -> bool) -> Vec<ImageEvent> {
+    let metadata_event = |path: &Path| {
+        catalog::companion_image(path).map(ImageEvent::MetadataUpdated)
+    };
+
+    // Moving a folder in or out only reports the folder itself, not its images
+    let folder_event = match &change {
+        Change::Created(p) | Change::Removed(p) if is_folder_path(p) => Some(p.clone()),
+        Change::Renamed(_, to) if is_folder_path(to) => Some(to.clone()),
+        _ => None,
+    };
+    if let Some(path) = folder_event {
+        return vec![ImageEvent::FolderChanged(path)];
+    }
+
+    match change {
+        Change::Created(path) if formats::is_image_path(&path) => vec![ImageEvent::Added(path)],
+        Change::Modified(path) if formats::is_image_path(&path) => vec![ImageEvent::Modified(path)],
+        Change::Removed(path) if formats::is_image_path(&path) => vec![ImageEvent::Removed(path)],
+        Change::Created(path) | Change::Modified(path) | Change::Removed(path) => {
+            if catalog::is_metadata_path(&path) {
+                metadata_event(&path).into_iter().collect()
+            } else {
+                Vec::new()
+            }
+        }
+        Change::Renamed(from, to) => {
+            match (formats::is_image_path(&from), formats::is_image_path(&to)) {
+                (true, true) => vec![ImageEvent::Renamed(from, to)],
+                // A temp file renamed into place; over an indexed image (e.g. an
+                // editor's atomic save) it replaces that image rather than adding one
+                (false, true) if is_cataloged(&to) => vec![ImageEvent::Modified(to)],
+                (false, true) => vec![ImageEvent::Added(to)],
+                (true, false) => vec![ImageEvent::Removed(from)],
+                (false, false) => [from, to]
+                    .iter()
+                    .filter(|p| catalog::is_metadata_path(p))
+                    .filter_map(|p| metadata_event(p))
+                    .collect(),
+            }
+        }
+    }
+}
+
+/// Update the catalog for a settled event and build the payload to emit.
+/// Returns None if there's nothing to tell the frontend (e.g. the file vanished).
+fn apply(app: &AppHandle, project_path: &str, event: ImageEvent) -> Option<BatchedEvent> {
+    let catalog = app.try_state::<CatalogState>()?;
+    let path_string = |path: &Path| path.to_string_lossy().to_string();
+    let update = |path: &Path| catalog.apply_change(project_path, path);
+    let image_payload = |path: &Path| {
+        catalog.get(project_path, path).and_then(|image| serde_json::to_value(image).ok())
+    };
+
+    let (event, payload) = match event {
+        ImageEvent::Added(path) => {
+            update(&path);
+            ("image-added", image_payload(&path)?)
+        }
+        ImageEvent::Modified(path) => {
+            update(&path);
+            ("image-modified", image_payload(&path)?)
+        }
+        ImageEvent::Removed(path) => {
+            // Already gone from the catalog, e.g. trashed through the app
+            catalog.get(project_path, &path)?;
+            update(&path);
+            let payload = ImageRemovedPayload { path: path_string(&path) };
+            ("image-removed", serde_json::to_value(payload).ok()?)
+        }
+        ImageEvent::Renamed(from, to) => {
+            update(&from);
+            update(&to);
+            let payload = ImageRenamedPayload {
+                from: path_string(&from),
+                to: path_string(&to),
+                image: catalog.get(project_path, &to),
+            };
+            ("image-renamed", serde_json::to_value(payload).ok()?)
+        }
+        ImageEvent::MetadataUpdated(image_path) => {
+            update(&image_path);
+            ("image-metadata-updated", image_payload(&image_path)?)
+        }
+        ImageEvent::FolderChanged(path) => {
+            if let Err(e) = catalog.reconcile(project_path) {
+                eprintln!("Failed to update catalog: {}", e);
+            }
+            let payload = FolderChangedPayload { path: path_string(&path) };
+            ("image-folder-changed", serde_json::to_value(payload).ok()?)
+        }
+    };
+
+    Some(BatchedEvent { event, payload })
+}
+
+/// Emit settled events; several settling together (e.g. `--count` variations)
+/// go out as a single `image-batch` so the gallery refreshes once.
+/// Commands that change the gallery themselves emit through here too.
+pub fn emit_settled(app: &AppHandle, project_path: &str, events: Vec<ImageEvent>) {
+    let mut batch: Vec<BatchedEvent> = events
+        .into_iter()
+        .filter_map(|event| apply(app, project_path, event))
+        .collect();
+
+    // Every window hears every event, so tag payloads with the project they belong to
+    for entry in &mut batch {
+        if let Some(payload) = entry.payload.as_object_mut() {
+            payload.insert("project_path".to_string(), project_path.into());
+        }
+    }
+
+    if batch.len() > 1 {
+        let _ = app.emit("image-batch", BatchPayload {
+            project_path: project_path.to_string(),
+            events: batch,
+        });
+    } else if let Some(single) = batch.pop() {
+        let _ = app.emit(single.event, single.payload);
+    }
+}
+
+/// Drop the watcher's events for changes the app made and reported itself,
+/// still bringing the catalog up to date with whatever is on disk now
+fn skip_own_changes(
+    app: &AppHandle,
+    project_path: &str,
+    own_changes: &Mutex<HashMap<PathBuf, Instant>>,
+    events: Vec<ImageEvent>,
+) -> Vec<ImageEvent> {
+    let mut own_changes = own_changes.lock();
+    own_changes.retain(|_, made| made.elapsed() < OWN_CHANGE_TIMEOUT);
+    if own_changes.is_empty() {
+        return events;
+    }
+
+    events
+        .into_iter()
+        .filter(|event| match event {
+            ImageEvent::Added(path) | ImageEvent::Modified(path) if own_changes.remove(path).is_some() => {
+                if let Some(catalog) = app.try_state::<CatalogState>() {
+                    catalog.apply_change(project_path, path);
+                }
+                false
+            }
+            _ => true,
+        })
+        .collect()
+}
+
+/// Events waiting to be emitted, shared by the notify callback and the emitter thread
+#[derive(Default)]
+struct EventQueue {
+    classifier: ChangeClassifier,
+    settler: Settler,
+}
+
+/// A running watch; dropping it stops the emitter thread
+struct WatchHandle {
+    _watcher: notify::RecommendedWatcher,
+    running: Arc<AtomicBool>,
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+/// A project's watch and how many callers asked for it
+struct WatchEntry {
+    /// Held only to keep the watch alive
+    _handle: WatchHandle,
+    subscribers: usize,
+}
+
+/// Gallery watchers for every open project, keyed by project path
 pub struct WatcherState {
-    watcher: Arc<Mutex<Option<notify::RecommendedWatcher>>>,
+    watchers: Arc<Mutex<HashMap<String, WatchEntry>>>,
+    /// Images the app wrote itself and already reported, and when
+    own_changes: Arc<Mutex<HashMap<PathBuf, Instant>>>,
 }
 
 impl WatcherState {
     pub fn new() -> Self {
         Self {
-            watcher: Arc::new(Mutex::new(None)),
+            watchers: Arc::new(Mutex::new(HashMap::new())),
+            own_changes: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
+    /// Note that the app is writing an image and reports the change itself,
+    /// so the watcher doesn't report it a second time
+    pub fn expect_own_change(&self, path: &Path) {
+        self.own_changes.lock().insert(path.to_path_buf(), Instant::now());
+    }
+
+    /// Start watching a project, or add a subscriber if it's already watched
+    /// (several windows can show the same project)
     pub fn start(&self, app: &AppHandle, path: &str) -> Result<()> {
+        let mut watchers = self.watchers.lock();
+        if let Some(entry) = watchers.get_mut(path) {
+            entry.subscribers += 1;
+            return Ok(());
+        }
+
+        let handle = Self::watch(app, path, self.own_changes.clone())?;
+        watchers.insert(path.to_string(), WatchEntry {
+            _handle: handle,
+            subscribers: 1,
+        });
+
+        Ok(())
+    }
+
+    fn watch(app: &AppHandle, path: &str, own_changes: Arc<Mutex<HashMap<PathBuf, Instant>>>) -> Result<WatchHandle> {
         let images_path = Path::new(path).join("generated_images");
-        let app_handle = app.clone();
+        let queue = Arc::new(Mutex::new(EventQueue::default()));
+        let running = Arc::new(AtomicBool::new(true));
 
+        let callback_queue = Arc::clone(&queue);
+        let watched_dir = images_path.clone();
+        let callback_app = app.clone();
+        let callback_project = path.to_string();
         let mut watcher = notify::recommended_watcher(move |res: Result<Event>| {
-            if let Ok(event) = res {
-                if let EventKind::Create(_) = event.kind {
-                    for path in event.paths {
-                        if let Some(ext) = path.extension() {
-                            let ext_lower = ext.to_string_lossy().to_lowercase();
-                            if matches!(ext_lower.as_str(), "png" | "jpg" | "jpeg") {
-                                let path_str = path.to_string_lossy().to_string();
-                                let _ = app_handle.emit("new-image", &path_str);
-                            }
-                        }
+            if let Ok(mut event) = res {
+                event.paths.retain(|p| !is_hidden(&watched_dir, p));
+                if event.paths.is_empty() {
+                    return;
+                }
+
+                let is_cataloged = |p: &Path| {
+                    callback_app
+                        .try_state::<CatalogState>()
+                        .is_some_and(|catalog| catalog.get(&callback_project, p).is_some())
+                };
+                let now = Instant::now();
+                let mut queue = callback_queue.lock();
+                for change in queue.classifier.classify(event) {
+                    for image_event in to_image_events(change, &is_cataloged) {
+                        queue.settler.push(image_event, now);
                     }
                 }
             }
         })?;
 
-        watcher.watch(&images_path, RecursiveMode::NonRecursive)?;
+        // Outputs are organised into client/campaign subfolders, so watch the whole tree
+        watcher.watch(&images_path, RecursiveMode::Recursive)?;
 
-        let mut guard = self.watcher.lock();
-        *guard = Some(watcher);
+        // Emit events once their files have finished being written
+        let app_handle = app.clone();
+        let project_path = path.to_string();
+        let thread_running = Arc::clone(&running);
+        thread::spawn(move || {
+            while thread_running.load(Ordering::Relaxed) {
+                thread::sleep(TICK_INTERVAL);
 
-        Ok(())
+                let now = Instant::now();
+                let ready = {
+                    let mut queue = queue.lock();
+                    if let Some(change) = queue.classifier.flush_stale(now) {
+                        // Only ever a removal, which doesn't need the catalog
+                        for image_event in to_image_events(change, &|_| false) {
+                            queue.settler.push(image_event, now);
+                        }
+                    }
+                    queue.settler.take_ready(now)
+                };
+
+                let ready = skip_own_changes(&app_handle, &project_path, &own_changes, ready);
+                if !ready.is_empty() {
+                    emit_settled(&app_handle, &project_path, ready);
+                }
+            }
+        });
+
+        Ok(WatchHandle {
+            _watcher: watcher,
+            running,
+        })
     }
 
-    pub fn stop(&self) {
-        let mut guard = self.watcher.lock();
-        *guard = None;
+    /// Drop a subscriber; the project stops being watched when none are left
+    pub fn stop(&self, path: &str) {
+        let mut watchers = self.watchers.lock();
+        let remaining = match watchers.get_mut(path) {
+            Some(entry) => {
+                entry.subscribers = entry.subscribers.saturating_sub(1);
+                entry.subscribers
+            }
+            None => return,
+        };
+
+        if remaining == 0 {
+            watchers.remove(path);
+        }
+    }
+
+    /// Stop watching every project
+    pub fn stop_all(&self) {
+        self.watchers.lock().clear();
+    }
+
+    /// Projects currently being watched
+    pub fn watched_projects(&self) -> Vec<String> {
+        let mut projects: Vec<String> = self.watchers.lock().keys().cloned().collect();
+        projects.sort();
+        projects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename(mode: RenameMode, paths: &[&str]) -> Event {
+        paths.iter().fold(
+            Event::new(EventKind::Modify(ModifyKind::Name(mode))),
+            |event, p| event.add_path(PathBuf::from(p)),
+        )
+    }
+
+    #[test]
+    fn test_classifier_pairs_rename_halves() {
+        let mut classifier = ChangeClassifier::default();
+
+        // inotify order: From, To, then Both
+        assert!(classifier.classify(rename(RenameMode::From, &["/g/a.png"])).is_empty());
+        let changes = classifier.classify(rename(RenameMode::To, &["/g/b.png"]));
+        assert!(matches!(
+            changes.as_slice(),
+            [Change::Renamed(from, to)] if from.ends_with("a.png") && to.ends_with("b.png")
+        ));
+        assert!(classifier.classify(rename(RenameMode::Both, &["/g/a.png", "/g/b.png"])).is_empty());
+
+        // A From with no To is a removal once something else happens
+        classifier.classify(rename(RenameMode::From, &["/g/c.png"]));
+        let create = Event::new(EventKind::Create(notify::event::CreateKind::File))
+            .add_path(PathBuf::from("/g/d.png"));
+        let changes = classifier.classify(create);
+        assert!(matches!(
+            changes.as_slice(),
+            [Change::Removed(removed), Change::Created(created)]
+                if removed.ends_with("c.png") && created.ends_with("d.png")
+        ));
     }
 }
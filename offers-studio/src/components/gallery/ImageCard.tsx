@@ -20,11 +20,16 @@ export function ImageCard({ image, onClick, onAddToReferences }: ImageCardProps)
       className="group relative aspect-square cursor-pointer overflow-hidden rounded-lg bg-gray-800 transition-transform hover:scale-[1.02]"
       onClick={onClick}
     >
-      <img
-        src={image.thumbnailUrl}
-        alt={image.metadata?.prompt || 'Generated image'}
-        className="h-full w-full object-cover"
-      />
+      {image.thumbnailUrl ? (
+        <img
+          src={image.thumbnailUrl}
+          alt={image.metadata?.prompt || 'Generated image'}
+          className="h-full w-full object-cover"
+        />
+      ) : (
+        // Thumbnail still being generated
+        <div className="h-full w-full animate-pulse bg-gray-700" />
+      )}
 
       {/* Hover Overlay */}
       <div className="absolute inset-0 bg-black/60 opacity-0 transition-opacity group-hover:opacity-100">
@@ -89,7 +89,11 @@ export function ImageModal({ image, onClose }: ImageModalProps) {
 
               <div>
                 <h4 className="mb-1 font-medium text-gray-400">Size</h4>
-                <p className="text-gray-200">{image.metadata?.size || 'N/A'}</p>
+                <p className="text-gray-200">
+                  {image.metadata?.image_size || 'N/A'}
+                  {image.properties &&
+                    ` (${image.properties.width}×${image.properties.height})`}
+                </p>
               </div>
 
               <div>
@@ -100,18 +104,16 @@ export function ImageModal({ image, onClose }: ImageModalProps) {
               </div>
             </div>
 
-            {/* Generation Time */}
-            {image.metadata?.generation_time && (
-              <div className="text-xs">
-                <span className="text-gray-400">Generation time:</span>{' '}
-                <span className="text-gray-200">
-                  {image.metadata.generation_time.toFixed(2)}s
-                </span>
+            {/* Description */}
+            {image.metadata?.description && (
+              <div>
+                <h4 className="mb-1 text-xs font-medium text-gray-400">Description</h4>
+                <p className="text-xs text-gray-200">{image.metadata.description}</p>
               </div>
             )}
 
             {/* Reference Images */}
-            {image.metadata?.reference_images &&
+            {image.metadata &&
               image.metadata.reference_images.length > 0 && (
                 <div>
                   <h4 className="mb-1 text-xs font-medium text-gray-400">
@@ -5,6 +5,7 @@ import { WebglAddon } from '@xterm/addon-webgl';
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
 import { ErrorBoundary } from '@/components/ErrorBoundary';
+import { PtyData, SavedTerminal } from '@/lib/types';
 import '@xterm/xterm/css/xterm.css';
 import '@/styles/terminal.css';
 
@@ -21,18 +22,46 @@ interface TerminalProps {
   onSessionChange?: (sessionId: string | null) => void;
 }
 
+// Terminals saved when the app last quit, fetched once and handed out as they're restored
+let savedTerminals: Promise<SavedTerminal[]> | null = null;
+
+async function takeSavedTerminal(projectPath: string): Promise<SavedTerminal | undefined> {
+  if (!savedTerminals) {
+    savedTerminals = invoke<SavedTerminal[]>('get_saved_terminals').catch((error) => {
+      console.error(error);
+      return [];
+    });
+  }
+  const terminals = await savedTerminals;
+  const index = terminals.findIndex((saved) => saved.info.cwd === projectPath);
+  return index === -1 ? undefined : terminals.splice(index, 1)[0];
+}
+
+// Hand a saved terminal back if the component unmounted before restoring it
+async function returnSavedTerminal(saved: SavedTerminal) {
+  (await savedTerminals)?.push(saved);
+}
+
 const TerminalInner = forwardRef<TerminalHandle, TerminalProps>(({ projectPath, onError, onSessionChange }, ref) => {
   const containerRef = useRef<HTMLDivElement>(null);
   const terminalRef = useRef<XTerm | null>(null);
   const ptyIdRef = useRef<string | null>(null);
   const [connectionError, setConnectionError] = useState<string | null>(null);
   const currentSessionIdRef = useRef<string | null>(null);
+  const writeChainRef = useRef<Promise<void>>(Promise.resolve());
+
+  // write_pty runs off the main thread, so chain writes to keep keystrokes in order
+  const writePty = (id: string, data: string) => {
+    writeChainRef.current = writeChainRef.current
+      .then(() => invoke<void>('write_pty', { id, data }))
+      .catch(console.error);
+  };
 
   // Expose sendInput, sendCommand, and resumeSession methods to parent components
   useImperativeHandle(ref, () => ({
     sendInput: (text: string) => {
       if (ptyIdRef.current) {
-        invoke('write_pty', { id: ptyIdRef.current, data: text }).catch(console.error);
+        writePty(ptyIdRef.current, text);
       }
     },
     sendCommand: (command: string) => {
@@ -40,11 +69,11 @@ const TerminalInner = forwardRef<TerminalHandle, TerminalProps>(({ projectPath,
         const id = ptyIdRef.current;
         // Clear any existing input first (Ctrl+U), then send command, then Enter
         // Small delay between command and Enter to let Claude Code process
-        invoke('write_pty', { id, data: '\x15' }).catch(console.error); // Ctrl+U to clear line
+        writePty(id, '\x15'); // Ctrl+U to clear line
         setTimeout(() => {
-          invoke('write_pty', { id, data: command }).catch(console.error);
+          writePty(id, command);
           setTimeout(() => {
-            invoke('write_pty', { id, data: '\r' }).catch(console.error); // Enter
+            writePty(id, '\r'); // Enter
           }, 50);
         }, 10);
       }
@@ -85,8 +114,11 @@ const TerminalInner = forwardRef<TerminalHandle, TerminalProps>(({ projectPath,
         currentSessionIdRef.current = sessionId;
 
         // Set up data listener for new PTY
-        const unlistenData = await listen<string>(`pty-data:${newPtyId}`, (event) => {
-          term.write(event.payload);
+        const unlistenData = await listen<PtyData>(`pty-data:${newPtyId}`, (event) => {
+          // Ack once xterm has processed the batch so the backend sends more
+          term.write(event.payload.data, () => {
+            invoke('ack_pty', { id: newPtyId }).catch(() => {});
+          });
         });
 
         // Set up exit listener
@@ -163,7 +195,7 @@ const TerminalInner = forwardRef<TerminalHandle, TerminalProps>(({ projectPath,
     term.attachCustomKeyEventHandler((event) => {
       if (event.key === 'Enter' && event.shiftKey) {
         if (event.type === 'keydown' && ptyIdRef.current) {
-          invoke('write_pty', { id: ptyIdRef.current, data: '\x1b[13;2u' });
+          writePty(ptyIdRef.current, '\x1b[13;2u');
         }
         return false; // Block all Shift+Enter events from xterm
       }
@@ -182,10 +214,14 @@ const TerminalInner = forwardRef<TerminalHandle, TerminalProps>(({ projectPath,
         const rows = term.rows;
         console.log(`Spawning PTY with size: ${cols}x${rows}`);
 
+        // Pick up where this project's terminal was when the app last quit
+        const saved = await takeSavedTerminal(cwd);
+        const resumeId = saved?.session_id ?? null;
+
         // Spawn Claude Code CLI
         const id = await invoke<string>('spawn_pty', {
           command: 'claude',
-          args: [],
+          args: resumeId ? ['--resume', resumeId] : [],
           cwd,
           cols,
           rows,
@@ -193,6 +229,7 @@ const TerminalInner = forwardRef<TerminalHandle, TerminalProps>(({ projectPath,
 
         if (isCleanedUp) {
           // Component unmounted during async operation
+          if (saved) returnSavedTerminal(saved);
           await invoke('kill_pty', { id });
           return;
         }
@@ -201,9 +238,21 @@ const TerminalInner = forwardRef<TerminalHandle, TerminalProps>(({ projectPath,
         ptyIdRef.current = id;
         setConnectionError(null);
 
+        if (saved) {
+          term.write(saved.scrollback);
+          term.write('\r\n\x1b[2m--- Restored from last session ---\x1b[0m\r\n');
+        }
+        if (resumeId) {
+          currentSessionIdRef.current = resumeId;
+          onSessionChange?.(resumeId);
+        }
+
         // Set up data listener for PTY output
-        const unlistenData = await listen<string>(`pty-data:${id}`, (event) => {
-          term.write(event.payload);
+        const unlistenData = await listen<PtyData>(`pty-data:${id}`, (event) => {
+          // Ack once xterm has processed the batch so the backend sends more
+          term.write(event.payload.data, () => {
+            invoke('ack_pty', { id: id }).catch(() => {});
+          });
         });
 
         // Set up exit listener
@@ -224,7 +273,7 @@ const TerminalInner = forwardRef<TerminalHandle, TerminalProps>(({ projectPath,
                 data.match(/^\x1b\[\d+;\d+[MmRt]$/)) {
               return; // Ignore mouse events
             }
-            invoke('write_pty', { id: ptyIdRef.current, data }).catch(console.error);
+            writePty(ptyIdRef.current, data);
           }
         });
 
@@ -5,6 +5,35 @@ import { invoke } from '@tauri-apps/api/core';
 interface UseFileWatcherOptions {
   projectPath: string;
   onNewImage?: (path: string) => void;
+  onImagesChanged?: (event: string) => void;
+}
+
+// Gallery events emitted by the Rust watcher
+const IMAGE_EVENTS = [
+  'image-added',
+  'image-modified',
+  'image-removed',
+  'image-renamed',
+  'image-metadata-updated',
+  'image-folder-changed',
+  // Several of the above that settled together
+  'image-batch',
+];
+
+// Every payload is tagged with the project it came from
+interface ImageEventPayload {
+  project_path: string;
+  path: string;
+}
+
+interface BatchedEvent {
+  event: string;
+  payload: ImageEventPayload;
+}
+
+interface BatchPayload {
+  project_path: string;
+  events: BatchedEvent[];
 }
 
 /**
@@ -14,14 +43,17 @@ interface UseFileWatcherOptions {
 export function useFileWatcher({
   projectPath,
   onNewImage,
+  onImagesChanged,
 }: UseFileWatcherOptions) {
-  // Use ref to store callback to avoid re-running effect when callback changes
+  // Use refs to store callbacks to avoid re-running effect when callbacks change
   const onNewImageRef = useRef(onNewImage);
   onNewImageRef.current = onNewImage;
+  const onImagesChangedRef = useRef(onImagesChanged);
+  onImagesChangedRef.current = onImagesChanged;
 
   // Start watcher and listen for events - only depends on projectPath
   useEffect(() => {
-    let unlisten: UnlistenFn | undefined;
+    const unlisteners: UnlistenFn[] = [];
     let isCleanedUp = false;
 
     const setup = async () => {
@@ -32,16 +64,30 @@ export function useFileWatcher({
 
         if (isCleanedUp) return;
 
-        // Listen to "new-image" event from backend (payload is just the path string)
-        unlisten = await listen<string>('new-image', (event) => {
-          const path = event.payload;
-          console.log('New image detected:', path);
+        // image-added carries the full image (with metadata); other payloads vary per event
+        for (const eventName of IMAGE_EVENTS) {
+          const unlisten = await listen<ImageEventPayload | BatchPayload>(eventName, (event) => {
+            // Ignore events from projects open in other windows
+            if (event.payload.project_path !== projectPath) return;
+            console.log(`Gallery event ${eventName}:`, event.payload);
+
+            const events: BatchedEvent[] = 'events' in event.payload
+              ? event.payload.events
+              : [{ event: eventName, payload: event.payload }];
+            events
+              .filter((e) => e.event === 'image-added')
+              .forEach((e) => onNewImageRef.current?.(e.payload.path));
 
-          // Only handle image files (png/jpg/jpeg)
-          if (/\.(png|jpe?g)$/i.test(path)) {
-            onNewImageRef.current?.(path);
+            // Notify once per emission so a batch only triggers one refresh
+            onImagesChangedRef.current?.(eventName);
+          });
+
+          if (isCleanedUp) {
+            unlisten();
+            return;
           }
-        });
+          unlisteners.push(unlisten);
+        }
       } catch (error) {
         console.error('Failed to start file watcher:', error);
       }
@@ -51,11 +97,9 @@ export function useFileWatcher({
 
     return () => {
       isCleanedUp = true;
-      if (unlisten) {
-        unlisten();
-      }
+      unlisteners.forEach((unlisten) => unlisten());
       // Stop the watcher when component unmounts
-      invoke('stop_watcher').catch(console.error);
+      invoke('stop_watcher', { path: projectPath }).catch(console.error);
     };
   }, [projectPath]); // Only re-run if projectPath changes
 }
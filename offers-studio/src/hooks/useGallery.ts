@@ -1,9 +1,17 @@
-import { useState, useEffect, useCallback } from 'react';
+import { useState, useEffect, useCallback, useRef } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { convertFileSrc } from '@tauri-apps/api/core';
 import { ImageFile, GalleryState } from '../lib/types';
 import { useFileWatcher } from './useFileWatcher';
 
+// Thumbnails generated at once; the rest wait their turn
+const THUMBNAIL_CONCURRENCY = 4;
+
+interface CachedThumbnail {
+  modified: number;
+  url: string;
+}
+
 /**
  * Hook to manage gallery state and image loading
  */
@@ -15,6 +23,52 @@ export function useGallery(projectPath: string) {
     selectedImage: null,
   });
 
+  // Thumbnail URLs by image path, reused until the image is modified
+  const thumbnailsRef = useRef(new Map<string, CachedThumbnail>());
+  // Images waiting for a thumbnail, and how many requests are in flight
+  const queueRef = useRef<ImageFile[]>([]);
+  const queuedRef = useRef(new Set<string>());
+  const activeRef = useRef(0);
+
+  const setThumbnail = useCallback((path: string, modified: number, url: string) => {
+    thumbnailsRef.current.set(path, { modified, url });
+    setState((prev) => ({
+      ...prev,
+      images: prev.images.map((img) =>
+        img.path === path && img.modified === modified ? { ...img, thumbnailUrl: url } : img
+      ),
+    }));
+  }, []);
+
+  // Work through the queue, a few thumbnails at a time
+  const pumpQueue = useCallback(() => {
+    while (activeRef.current < THUMBNAIL_CONCURRENCY && queueRef.current.length > 0) {
+      const img = queueRef.current.shift()!;
+      activeRef.current += 1;
+
+      invoke<string>('get_thumbnail', { projectPath, path: img.path, size: 'small' })
+        // Fall back to the full image for formats we can't thumbnail
+        .catch(() => img.path)
+        .then((thumbnailPath) => setThumbnail(img.path, img.modified, convertFileSrc(thumbnailPath)))
+        .finally(() => {
+          activeRef.current -= 1;
+          queuedRef.current.delete(`${img.path}:${img.modified}`);
+          pumpQueue();
+        });
+    }
+  }, [projectPath, setThumbnail]);
+
+  // Queue thumbnails for images that are new or changed since their thumbnail was made
+  const queueThumbnails = useCallback((images: ImageFile[]) => {
+    for (const img of images) {
+      const key = `${img.path}:${img.modified}`;
+      if (img.thumbnailUrl || queuedRef.current.has(key)) continue;
+      queuedRef.current.add(key);
+      queueRef.current.push(img);
+    }
+    pumpQueue();
+  }, [pumpQueue]);
+
   // Load images from backend
   const loadImages = useCallback(async () => {
     try {
@@ -24,22 +78,31 @@ export function useGallery(projectPath: string) {
         projectPath,
       });
 
-      // Convert file paths to URLs that Tauri can serve
-      const imagesWithUrls = images.map((img) => ({
-        ...img,
-        fullUrl: convertFileSrc(img.path),
-        thumbnailUrl: convertFileSrc(img.path), // Could add thumbnail generation later
-        timestamp: new Date(img.timestamp),
-      }));
+      // Show the list straight away; thumbnails fill in as they're ready
+      const imagesWithUrls = images.map((img) => {
+        const cached = thumbnailsRef.current.get(img.path);
+        return {
+          ...img,
+          fullUrl: convertFileSrc(img.path),
+          thumbnailUrl: cached?.modified === img.modified ? cached.url : undefined,
+          timestamp: new Date(img.timestamp),
+        };
+      });
 
       // Sort by timestamp descending (newest first)
       imagesWithUrls.sort((a, b) => b.timestamp.getTime() - a.timestamp.getTime());
 
+      // Drop queued work for images that are gone or have changed again
+      queueRef.current = queueRef.current.filter((queued) =>
+        imagesWithUrls.some((img) => img.path === queued.path && img.modified === queued.modified)
+      );
+
       setState((prev) => ({
         ...prev,
         images: imagesWithUrls,
         loading: false,
       }));
+      queueThumbnails(imagesWithUrls);
     } catch (error) {
       console.error('Failed to load images:', error);
       setState((prev) => ({
@@ -48,18 +111,21 @@ export function useGallery(projectPath: string) {
         error: error instanceof Error ? error.message : 'Failed to load images',
       }));
     }
-  }, [projectPath]);
+  }, [projectPath, queueThumbnails]);
 
   // Initial load
   useEffect(() => {
+    thumbnailsRef.current.clear();
+    queueRef.current = [];
     loadImages();
   }, [loadImages]);
 
-  // Listen for new images - starts the file watcher on mount
+  // Listen for gallery changes - starts the file watcher on mount.
+  // Reloading the list is cheap; only added or changed images get new thumbnails.
   useFileWatcher({
     projectPath,
-    onNewImage: () => {
-      console.log('Gallery: reloading images due to new-image event');
+    onImagesChanged: (event) => {
+      console.log(`Gallery: reloading images due to ${event} event`);
       loadImages();
     },
   });
@@ -3,20 +3,51 @@
  */
 
 export interface ImageMetadata {
-  prompt: string;
-  model: string;
-  timestamp: string;
+  schema_version: number;
+  prompt?: string;
+  model?: string;
+  timestamp?: string;
   aspect_ratio?: string;
-  size?: string;
-  generation_time?: number;
-  reference_images?: string[];
+  image_size?: string;
+  reference_images: string[];
+  description?: string;
+  // Fields written by newer generators are passed through untouched
+  [key: string]: unknown;
+}
+
+export interface ImageProperties {
+  width: number;
+  height: number;
+  bit_depth?: number;
+  color_type?: string;
+  has_icc_profile: boolean;
+  // PNG text chunks by keyword
+  text: Record<string, string>;
+}
+
+export interface Annotation {
+  tags: string[];
+  // 1 to 5 stars
+  rating?: number;
+  favorite: boolean;
+  notes?: string;
 }
 
 export interface ImageFile {
   path: string;
   filename: string;
+  // Subfolder of generated_images ('' for the top level)
+  folder: string;
   timestamp: Date;
+  // File modification time in ms, changes whenever the image is rewritten
+  modified: number;
+  // Detected from file contents, e.g. image/webp
+  mime_type: string;
   metadata?: ImageMetadata;
+  // Read from the file header; missing for formats we can't parse
+  properties?: ImageProperties;
+  // Tags, rating and notes added in the app
+  annotation?: Annotation;
   thumbnailUrl?: string;
   fullUrl?: string;
 }
@@ -65,3 +96,17 @@ export interface SessionInfo {
   message_count: number;
   custom_name?: string;
 }
+
+export interface PtyData {
+  data: string;
+  /** Byte offset of the end of `data` in the PTY's output; events at or below a scrollback snapshot's offset are already in it */
+  offset: number;
+}
+
+/** A terminal that was running when the app last quit (only the fields the UI uses) */
+export interface SavedTerminal {
+  info: { command: string; args: string[]; cwd: string };
+  /** Claude Code session to resume, or null if it couldn't be worked out */
+  session_id: string | null;
+  scrollback: string;
+}